@@ -0,0 +1,419 @@
+//! `Read` adapters over the combined input.
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use FileInput;
+use strategy::IoStrategy;
+
+
+/// A reader that applies a function to every byte of a `FileInput`.
+///
+/// This struct is created by `FileInput::map_bytes`.
+pub struct MapBytes<Io: IoStrategy, F> {
+    input: FileInput<Io>,
+    f: F,
+}
+
+impl<Io: IoStrategy, F> MapBytes<Io, F> {
+    pub(crate) fn new(input: FileInput<Io>, f: F) -> Self {
+        MapBytes { input, f }
+    }
+}
+
+impl<Io, F> Read for MapBytes<Io, F>
+    where Io: IoStrategy,
+          F: FnMut(u8) -> u8
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.input.read(buf)?;
+        for byte in &mut buf[..bytes_read] {
+            *byte = (self.f)(*byte);
+        }
+        Ok(bytes_read)
+    }
+}
+
+/// A reader that copies every byte it reads from a `FileInput` to a writer.
+///
+/// This struct is created by `FileInput::tee`.
+pub struct Tee<Io: IoStrategy, W> {
+    input: FileInput<Io>,
+    writer: W,
+}
+
+impl<Io: IoStrategy, W> Tee<Io, W> {
+    pub(crate) fn new(input: FileInput<Io>, writer: W) -> Self {
+        Tee { input, writer }
+    }
+
+    /// Unwraps this `Tee`, returning the writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+impl<Io: IoStrategy, W: Write> Read for Tee<Io, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.input.read(buf)?;
+        self.writer.write_all(&buf[..bytes_read])?;
+        Ok(bytes_read)
+    }
+}
+
+/// A shareable handle reporting how much data has passed through a `Metered` reader.
+///
+/// `Meter`s are created by `FileInput::metered` and can be cloned and queried from any thread.
+#[derive(Debug, Clone)]
+pub struct Meter {
+    bytes: Arc<AtomicU64>,
+    started: Instant,
+}
+
+impl Meter {
+    /// Returns the total number of bytes read so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the time elapsed since the meter was created.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns the average throughput since the meter was created, in bytes per second.
+    pub fn bytes_per_second(&self) -> f64 {
+        let seconds = self.elapsed().as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A reader that counts the bytes read from a `FileInput` into a `Meter`.
+///
+/// This struct is created by `FileInput::metered`.
+pub struct Metered<Io: IoStrategy> {
+    input: FileInput<Io>,
+    meter: Meter,
+}
+
+impl<Io: IoStrategy> Metered<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> (Self, Meter) {
+        let meter = Meter {
+            bytes: Arc::new(AtomicU64::new(0)),
+            started: Instant::now(),
+        };
+        let metered = Metered {
+            input,
+            meter: meter.clone(),
+        };
+        (metered, meter)
+    }
+}
+
+impl<Io: IoStrategy> Read for Metered<Io> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.input.read(buf)?;
+        self.meter.bytes.fetch_add(bytes_read as u64, Ordering::Relaxed);
+        Ok(bytes_read)
+    }
+}
+
+/// A reader that collapses runs of blank lines in a `FileInput` into a single blank line.
+///
+/// This struct is created by `FileInput::squeeze_blank_lines`.
+pub struct SqueezeBlankLines<Io: IoStrategy> {
+    input: FileInput<Io>,
+    newlines: usize,
+}
+
+impl<Io: IoStrategy> SqueezeBlankLines<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        // The start of the input counts as the end of a line, so leading blank lines squeeze.
+        SqueezeBlankLines { input, newlines: 1 }
+    }
+}
+
+impl<Io: IoStrategy> Read for SqueezeBlankLines<Io> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let bytes_read = self.input.read(buf)?;
+            if bytes_read == 0 {
+                return Ok(0);
+            }
+
+            let mut kept = 0;
+            for i in 0..bytes_read {
+                let byte = buf[i];
+                if byte == b'\n' {
+                    self.newlines += 1;
+                    if self.newlines > 2 {
+                        continue;
+                    }
+                } else {
+                    self.newlines = 0;
+                }
+                buf[kept] = byte;
+                kept += 1;
+            }
+
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+/// A reader that prefixes every line of a `FileInput` with the name of its source.
+///
+/// This struct is created by `FileInput::prefix_source`.
+pub struct PrefixSource<Io: IoStrategy> {
+    input: FileInput<Io>,
+    chunk: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+    at_line_start: bool,
+}
+
+impl<Io: IoStrategy> PrefixSource<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        let chunk = vec![0; input.chunk_size];
+        PrefixSource {
+            input,
+            chunk,
+            output: Vec::new(),
+            position: 0,
+            at_line_start: true,
+        }
+    }
+}
+
+impl<Io: IoStrategy> Read for PrefixSource<Io> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.output.len() {
+            let bytes_read = self.input.read(&mut self.chunk)?;
+            if bytes_read == 0 {
+                return Ok(0);
+            }
+
+            let prefix = match self.input.source() {
+                Some(source) => format!("{}:", source),
+                None => String::new(),
+            };
+            self.output.clear();
+            self.position = 0;
+            for &byte in &self.chunk[..bytes_read] {
+                if self.at_line_start {
+                    self.output.extend_from_slice(prefix.as_bytes());
+                }
+                self.output.push(byte);
+                self.at_line_start = byte == b'\n';
+            }
+        }
+
+        let count = (&self.output[self.position..]).read(buf)?;
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// A reader that passes every line of a `FileInput` through a function that can replace or
+/// drop it.
+///
+/// This struct is created by `FileInput::filter_lines`.
+pub struct FilterLines<Io: IoStrategy, F> {
+    input: BufReader<FileInput<Io>>,
+    f: F,
+    line: String,
+    output: Vec<u8>,
+    position: usize,
+}
+
+impl<Io: IoStrategy, F> FilterLines<Io, F> {
+    pub(crate) fn new(input: FileInput<Io>, f: F) -> Self {
+        FilterLines {
+            input: BufReader::with_capacity(input.chunk_size, input),
+            f,
+            line: String::new(),
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<Io, F> Read for FilterLines<Io, F>
+    where Io: IoStrategy,
+          F: FnMut(&str) -> Option<String>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.output.len() {
+            self.line.clear();
+            if self.input.read_line(&mut self.line)? == 0 {
+                return Ok(0);
+            }
+
+            let newline = if self.line.ends_with("\r\n") {
+                "\r\n"
+            } else if self.line.ends_with('\n') {
+                "\n"
+            } else {
+                ""
+            };
+            let len = self.line.len() - newline.len();
+            self.line.truncate(len);
+            if let Some(line) = (self.f)(&self.line) {
+                self.output.clear();
+                self.position = 0;
+                self.output.extend_from_slice(line.as_bytes());
+                self.output.extend_from_slice(newline.as_bytes());
+            }
+        }
+
+        let count = (&self.output[self.position..]).read(buf)?;
+        self.position += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{ErrorKind, Read};
+    use std::thread;
+
+    use FileInput;
+    use test_util::MockIo;
+
+    #[test]
+    fn map_bytes_round_trip() {
+        let paths = vec!["testdata/1"];
+        let mut masked = Vec::new();
+        FileInput::new(&paths).map_bytes(|b| b ^ 0x20).read_to_end(&mut masked).unwrap();
+
+        assert_eq!(masked, b"oNE\x0e*");
+
+        let io = MockIo::new().file("masked", &masked);
+        let paths = vec!["masked"];
+        let mut unmasked = String::new();
+        FileInput::with_strategy(&paths, io)
+            .map_bytes(|b| b ^ 0x20)
+            .read_to_string(&mut unmasked)
+            .unwrap();
+
+        assert_eq!(unmasked, "One.\n");
+    }
+
+    #[test]
+    fn squeeze_blank_lines_across_sources() {
+        let paths = vec!["testdata/blank_end", "testdata/blank_start"];
+        let mut output = String::new();
+
+        FileInput::new(&paths).squeeze_blank_lines().read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, "Head.\n\nTail.\n");
+    }
+
+    #[test]
+    fn squeeze_leading_blank_lines() {
+        let io = MockIo::new().file("a", b"\n\n\nA.\n");
+        let mut output = String::new();
+
+        FileInput::with_strategy(&["a"], io).squeeze_blank_lines().read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!(output, "\nA.\n");
+    }
+
+    #[test]
+    fn prefix_source() {
+        let paths = vec!["testdata/1", "testdata/2"];
+        let mut output = String::new();
+
+        FileInput::new(&paths)
+            .read_chunk_size(3)
+            .prefix_source()
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!(output, "testdata/1:One.\ntestdata/2:Two.\ntestdata/2:Two.\n");
+    }
+
+    #[test]
+    fn filter_lines() {
+        let paths = vec!["testdata/1", "testdata/2", "testdata/1"];
+        let mut output = String::new();
+        FileInput::new(&paths)
+            .filter_lines(|line| if line.contains("Two") { None } else { Some(line.to_string()) })
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!(output, "One.\nOne.\n");
+    }
+
+    #[test]
+    fn filter_lines_across_sources() {
+        let io = MockIo::new().file("a", b"Hea").file("b", b"d.\nTail").trickle();
+        let paths = vec!["a", "b"];
+        let mut output = String::new();
+        FileInput::with_strategy(&paths, io)
+            .filter_lines(|line| Some(line.to_uppercase()))
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!(output, "HEAD.\nTAIL");
+    }
+
+    #[test]
+    fn filter_lines_strips_crlf() {
+        let io = MockIo::new().file("a", b"One.\r\nTwo.\r\nThree.\n");
+        let paths = vec!["a"];
+        let mut output = String::new();
+        FileInput::with_strategy(&paths, io)
+            .filter_lines(|line| if line == "Two." { None } else { Some(format!("<{}>", line)) })
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!(output, "<One.>\r\n<Three.>\n");
+    }
+
+    #[test]
+    fn tee() {
+        let paths = vec!["testdata/1", "testdata/2"];
+        let mut copy = Vec::new();
+        let mut output = Vec::new();
+
+        FileInput::new(&paths).tee(&mut copy).read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, b"One.\nTwo.\nTwo.\n");
+        assert_eq!(copy, output);
+    }
+
+    #[test]
+    fn tee_write_error_is_read_error() {
+        let paths = vec!["testdata/1"];
+        let mut full = [0u8; 2];
+        let mut output = Vec::new();
+
+        let result = FileInput::new(&paths).tee(&mut full[..]).read_to_end(&mut output);
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn metered() {
+        let paths = vec!["testdata/1", "testdata/2"];
+        let (mut reader, meter) = FileInput::new(&paths).metered();
+        let observer = meter.clone();
+        let mut output = Vec::new();
+
+        reader.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output.len(), 15);
+        assert_eq!(meter.bytes(), 15);
+        assert_eq!(thread::spawn(move || observer.bytes()).join().unwrap(), 15);
+    }
+}
@@ -0,0 +1,203 @@
+//! Iteration over raw read chunks.
+use std::io;
+use std::io::Read;
+
+use {FileInput, Source};
+use strategy::IoStrategy;
+
+
+/// An iterator over the chunks returned by each `read` of a `FileInput`, paired with the source
+/// they came from.
+///
+/// Each `read` is given a buffer of `FileInput::read_chunk_size` bytes.
+///
+/// This struct is created by `FileInput::chunks`.
+pub struct Chunks<Io: IoStrategy> {
+    input: FileInput<Io>,
+}
+
+impl<Io: IoStrategy> Chunks<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        Chunks { input }
+    }
+}
+
+impl<Io: IoStrategy> Iterator for Chunks<Io> {
+    type Item = io::Result<(Source, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = vec![0; self.input.chunk_size];
+        match self.input.read(&mut chunk) {
+            Ok(0) => None,
+            Ok(bytes_read) => {
+                chunk.truncate(bytes_read);
+                Some(Ok((self.input.origin().1, chunk)))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// An iterator over the full contents of each source as a separate document.
+///
+/// This struct is created by `FileInput::document_per_file`.
+pub struct Documents<Io: IoStrategy> {
+    input: FileInput<Io>,
+}
+
+impl<Io: IoStrategy> Documents<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        Documents { input }
+    }
+}
+
+impl<Io: IoStrategy> Iterator for Documents<Io> {
+    type Item = io::Result<(Source, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (source, opened) = self.input.open_next_source()?;
+        let mut document = Vec::new();
+        let result = opened.and_then(|mut reader| reader.read_to_end(&mut document));
+        Some(result.map(|_| (source, document)))
+    }
+}
+
+/// An iterator pairing each source with the result of opening it.
+///
+/// This struct is created by `FileInput::into_source_readers`.
+pub struct SourceReaders<Io: IoStrategy> {
+    input: FileInput<Io>,
+}
+
+impl<Io: IoStrategy> SourceReaders<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        SourceReaders { input }
+    }
+}
+
+impl<Io: IoStrategy> Iterator for SourceReaders<Io> {
+    type Item = (Source, io::Result<Box<dyn Read + Send>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.input.open_next_source()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{ErrorKind, Read};
+
+    use std::fs;
+
+    use {FileInput, Source};
+    use test_util::{MockIo, TempDir};
+
+    #[test]
+    fn chunks_by_source() {
+        let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+        let mut by_source: Vec<(Source, Vec<u8>)> = Vec::new();
+
+        for chunk in FileInput::new(&paths).chunks() {
+            let (source, data) = chunk.unwrap();
+            match by_source.last_mut() {
+                Some(&mut (ref last, ref mut content)) if *last == source => {
+                    content.extend_from_slice(&data)
+                }
+                _ => by_source.push((source, data)),
+            }
+        }
+
+        assert_eq!(by_source,
+                   [(Source::File("testdata/1".to_string()), b"One.\n".to_vec()),
+                    (Source::File("testdata/2".to_string()), b"Two.\nTwo.\n".to_vec())]);
+    }
+
+    #[test]
+    fn document_per_file() {
+        let paths = vec!["testdata/a.json", "testdata/empty", "testdata/b.json"];
+        let documents: Vec<(Source, Vec<u8>)> =
+            FileInput::new(&paths).document_per_file().map(|d| d.unwrap()).collect();
+
+        assert_eq!(documents,
+                   [(Source::File("testdata/a.json".to_string()), b"{\"id\": 1}\n".to_vec()),
+                    (Source::File("testdata/empty".to_string()), b"".to_vec()),
+                    (Source::File("testdata/b.json".to_string()),
+                     b"{\n  \"id\": 2\n}\n".to_vec())]);
+    }
+
+    #[test]
+    fn document_per_file_continues_after_error() {
+        let paths = vec!["testdata/NOPE", "testdata/a.json"];
+        let mut documents = FileInput::new(&paths).document_per_file();
+
+        assert_eq!(documents.next().unwrap().unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(documents.next().unwrap().unwrap().0,
+                   Source::File("testdata/a.json".to_string()));
+        assert!(documents.next().is_none());
+    }
+
+    #[test]
+    fn chunks_use_read_chunk_size() {
+        let io = MockIo::new().file("a", &[b'x'; 10]);
+        let paths = vec!["a"];
+        let sizes: Vec<usize> = FileInput::with_strategy(&paths, io)
+            .read_chunk_size(4)
+            .chunks()
+            .map(|c| c.unwrap().1.len())
+            .collect();
+
+        assert_eq!(sizes, [4, 4, 2]);
+    }
+
+    #[test]
+    fn into_source_readers_isolates_errors() {
+        let paths = vec!["testdata/1", "testdata/NOPE", "testdata/2"];
+        let readers: Vec<_> = FileInput::new(&paths).into_source_readers().collect();
+
+        assert_eq!(readers.len(), 3);
+        assert_eq!(readers[1].0, Source::File("testdata/NOPE".to_string()));
+        assert_eq!(readers[1].1.as_ref().err().unwrap().kind(), ErrorKind::NotFound);
+
+        let mut contents = String::new();
+        for (_, reader) in readers {
+            if let Ok(mut reader) = reader {
+                reader.read_to_string(&mut contents).unwrap();
+            }
+        }
+        assert_eq!(contents, "One.\nTwo.\nTwo.\n");
+    }
+
+    #[test]
+    fn into_source_readers_apply_open_options() {
+        let dir = TempDir::new("source_readers_options");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("a"), "A.\n").unwrap();
+        fs::write(dir.join("sub/b"), "B.\n").unwrap();
+        let paths = vec![dir.path().to_str().unwrap(), "testdata/binary", "testdata/1",
+                         "testdata/1_copy", "testdata/NOPE"];
+        let mut contents = String::new();
+
+        for (_, reader) in FileInput::new(&paths)
+            .recursive()
+            .skip_binary()
+            .skip_duplicate_content()
+            .missing_source_placeholder(b"Missing.\n".to_vec())
+            .into_source_readers() {
+            reader.unwrap().read_to_string(&mut contents).unwrap();
+        }
+
+        assert_eq!(contents, "A.\nB.\nOne.\nMissing.\n");
+    }
+
+    #[test]
+    fn document_per_file_skips_header_source() {
+        let paths = vec!["testdata/1", "testdata/2"];
+        let documents: Vec<Vec<u8>> = FileInput::new(&paths)
+            .header_source()
+            .document_per_file()
+            .map(|d| d.unwrap().1)
+            .collect();
+
+        assert_eq!(documents, [b"Two.\nTwo.\n".to_vec()]);
+    }
+}
@@ -0,0 +1,209 @@
+//! A minimal CSV record parser over each source.
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::mem;
+
+use FileInput;
+use strategy::IoStrategy;
+
+
+/// An iterator over the CSV records of every source.
+///
+/// This struct is created by `FileInput::csv_records`. Fields may be quoted with `"`, in which
+/// case they can contain commas, newlines, and doubled `""` quotes. Records never span sources,
+/// and blank lines are skipped.
+pub struct CsvRecords<Io: IoStrategy> {
+    input: FileInput<Io>,
+    current: Option<BufReader<Box<dyn Read + Send>>>,
+    header_emitted: bool,
+    skip_repeated_headers: bool,
+}
+
+impl<Io: IoStrategy> CsvRecords<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        CsvRecords {
+            input,
+            current: None,
+            header_emitted: false,
+            skip_repeated_headers: false,
+        }
+    }
+
+    /// Treats the first record of each source as a header, yielding only the first one read.
+    ///
+    /// Sources without any records don't count, so the header comes from the first source that
+    /// has one.
+    pub fn skip_repeated_headers(mut self) -> Self {
+        self.skip_repeated_headers = true;
+        self
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn finish_field(fields: &mut Vec<String>, field: &mut Vec<u8>) -> io::Result<()> {
+    let field = String::from_utf8(mem::take(field))
+        .map_err(|_| invalid_data("CSV field is not valid UTF-8"))?;
+    fields.push(field);
+    Ok(())
+}
+
+fn read_record<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<String>>> {
+    let mut fields = Vec::new();
+    let mut field = Vec::new();
+    let mut in_quotes = false;
+    let mut started = false;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            if in_quotes {
+                return Err(invalid_data("unterminated quoted CSV field"));
+            }
+            if !started {
+                return Ok(None);
+            }
+            finish_field(&mut fields, &mut field)?;
+            return Ok(Some(fields));
+        }
+
+        if !started && (line == b"\n" || line == b"\r\n") {
+            continue;
+        }
+        started = true;
+
+        let mut bytes = line.iter().cloned().peekable();
+        while let Some(byte) = bytes.next() {
+            if in_quotes {
+                if byte != b'"' {
+                    field.push(byte);
+                } else if bytes.peek() == Some(&b'"') {
+                    field.push(b'"');
+                    bytes.next();
+                } else {
+                    in_quotes = false;
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' if field.is_empty() => in_quotes = true,
+                b',' => finish_field(&mut fields, &mut field)?,
+                b'\r' if bytes.peek() == Some(&b'\n') => {}
+                b'\n' => {
+                    finish_field(&mut fields, &mut field)?;
+                    return Ok(Some(fields));
+                }
+                _ => field.push(byte),
+            }
+        }
+    }
+}
+
+impl<Io: IoStrategy> Iterator for CsvRecords<Io> {
+    type Item = io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current.is_none() {
+                let (_, opened) = self.input.open_next_source()?;
+                match opened {
+                    Ok(reader) => self.current = Some(BufReader::new(reader)),
+                    Err(e) => return Some(Err(e)),
+                }
+                if self.skip_repeated_headers && self.header_emitted {
+                    match read_record(self.current.as_mut().unwrap()) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            self.current = None;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            }
+
+            match read_record(self.current.as_mut().unwrap()) {
+                Ok(Some(record)) => {
+                    self.header_emitted = true;
+                    return Some(Ok(record));
+                }
+                Ok(None) => self.current = None,
+                Err(e) => {
+                    self.current = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use FileInput;
+    use test_util::MockIo;
+
+    fn records(data: &[u8]) -> Vec<Vec<String>> {
+        let io = MockIo::new().file("a.csv", data);
+        let paths = vec!["a.csv"];
+        FileInput::with_strategy(&paths, io).csv_records().map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn quoted_fields() {
+        let parsed =
+            records(b"name,note\r\n\"Smith, J\",\"line one\nline \"\"two\"\"\"\n\nlast,\n");
+
+        assert_eq!(parsed,
+                   [vec!["name", "note"],
+                    vec!["Smith, J", "line one\nline \"two\""],
+                    vec!["last", ""]]);
+    }
+
+    #[test]
+    fn unterminated_final_record() {
+        assert_eq!(records(b"a,b\nc,d"), [vec!["a", "b"], vec!["c", "d"]]);
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let io = MockIo::new().file("a.csv", b"\"open\n");
+        let paths = vec!["a.csv"];
+        let mut records = FileInput::with_strategy(&paths, io).csv_records();
+
+        assert_eq!(records.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn skip_repeated_headers() {
+        let io = MockIo::new().file("a.csv", b"h1,h2\n1,2\n").file("b.csv", b"h1,h2\n3,4\n");
+        let paths = vec!["a.csv", "b.csv"];
+        let parsed: Vec<Vec<String>> = FileInput::with_strategy(&paths, io)
+            .csv_records()
+            .skip_repeated_headers()
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert_eq!(parsed, [vec!["h1", "h2"], vec!["1", "2"], vec!["3", "4"]]);
+    }
+
+    #[test]
+    fn skip_repeated_headers_after_empty_source() {
+        let io = MockIo::new()
+            .file("empty.csv", b"")
+            .file("a.csv", b"h1,h2\n1,2\n")
+            .file("b.csv", b"h1,h2\n3,4\n");
+        let paths = vec!["empty.csv", "a.csv", "b.csv"];
+        let parsed: Vec<Vec<String>> = FileInput::with_strategy(&paths, io)
+            .csv_records()
+            .skip_repeated_headers()
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert_eq!(parsed, [vec!["h1", "h2"], vec!["1", "2"], vec!["3", "4"]]);
+    }
+}
@@ -0,0 +1,317 @@
+//! Fixed-size, length-prefixed, and delimited framing of the combined input.
+use std::io;
+use std::io::Read;
+use std::mem;
+
+use {FileInput, Source};
+use strategy::IoStrategy;
+
+
+/// An iterator over fixed-size chunks of a `FileInput`.
+///
+/// This struct is created by `FileInput::frames`. By default frames may span source
+/// boundaries; see `within_sources` to change that.
+pub struct Frames<Io: IoStrategy> {
+    input: FileInput<Io>,
+    size: usize,
+    within_sources: bool,
+    pending: Option<((usize, Source), Vec<u8>)>,
+}
+
+impl<Io: IoStrategy> Frames<Io> {
+    pub(crate) fn new(input: FileInput<Io>, size: usize) -> Self {
+        assert!(size > 0, "frame size must be non-zero");
+        Frames {
+            input,
+            size,
+            within_sources: false,
+            pending: None,
+        }
+    }
+
+    /// Never lets a frame span two sources.
+    ///
+    /// The last frame of each source may then be shorter than the frame size.
+    pub fn within_sources(mut self) -> Self {
+        self.within_sources = true;
+        self
+    }
+}
+
+impl<Io: IoStrategy> Iterator for Frames<Io> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (mut frame_source, mut frame) = match self.pending.take() {
+            Some((origin, data)) => (Some(origin), data),
+            None => (None, Vec::with_capacity(self.size)),
+        };
+
+        while frame.len() < self.size {
+            let start = frame.len();
+            frame.resize(self.size, 0);
+            let bytes_read = match self.input.read(&mut frame[start..]) {
+                Ok(bytes_read) => bytes_read,
+                Err(e) => return Some(Err(e)),
+            };
+            frame.truncate(start + bytes_read);
+
+            if bytes_read == 0 {
+                break;
+            }
+
+            if self.within_sources {
+                let origin = self.input.origin();
+                match frame_source {
+                    Some(ref current) if *current != origin => {
+                        self.pending = Some((origin, frame.split_off(start)));
+                        break;
+                    }
+                    _ => frame_source = Some(origin),
+                }
+            }
+        }
+
+        if frame.is_empty() {
+            None
+        } else {
+            Some(Ok(frame))
+        }
+    }
+}
+
+/// The byte order of the length prefixes read by `FileInput::length_prefixed_frames`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// Most significant byte first.
+    Big,
+    /// Least significant byte first.
+    Little,
+}
+
+/// An iterator over records of a `FileInput` that are each preceded by a 4-byte length.
+///
+/// This struct is created by `FileInput::length_prefixed_frames`.
+pub struct LengthPrefixedFrames<Io: IoStrategy> {
+    input: FileInput<Io>,
+    endian: Endian,
+}
+
+impl<Io: IoStrategy> LengthPrefixedFrames<Io> {
+    pub(crate) fn new(input: FileInput<Io>, endian: Endian) -> Self {
+        LengthPrefixedFrames { input, endian }
+    }
+
+    /// Fills `buf` as far as possible, returning how much was filled before the input ended.
+    fn fill(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.input.read(&mut buf[filled..])? {
+                0 => break,
+                bytes_read => filled += bytes_read,
+            }
+        }
+        Ok(filled)
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut prefix = [0; 4];
+        match self.fill(&mut prefix)? {
+            0 => return Ok(None),
+            4 => {}
+            _ => return Err(truncated("length prefix")),
+        }
+
+        let len = match self.endian {
+            Endian::Big => u32::from_be_bytes(prefix),
+            Endian::Little => u32::from_le_bytes(prefix),
+        };
+        // A corrupt length shouldn't cause a huge allocation up front.
+        let mut frame = Vec::new();
+        if self.input.by_ref().take(len as u64).read_to_end(&mut frame)? < len as usize {
+            return Err(truncated("frame"));
+        }
+        Ok(Some(frame))
+    }
+}
+
+fn truncated(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, format!("input ended within a {}", what))
+}
+
+impl<Io: IoStrategy> Iterator for LengthPrefixedFrames<Io> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+/// An iterator over records of a `FileInput` separated by a multi-byte delimiter.
+///
+/// This struct is created by `FileInput::split_on_bytes`.
+pub struct SplitOnBytes<Io: IoStrategy> {
+    input: FileInput<Io>,
+    delim: Vec<u8>,
+    chunk: Vec<u8>,
+    data: Vec<u8>,
+    searched: usize,
+    done: bool,
+}
+
+impl<Io: IoStrategy> SplitOnBytes<Io> {
+    pub(crate) fn new(input: FileInput<Io>, delim: &[u8]) -> Self {
+        assert!(!delim.is_empty(), "delimiter must be non-empty");
+        let chunk = vec![0; input.chunk_size];
+        SplitOnBytes {
+            input,
+            delim: delim.to_vec(),
+            chunk,
+            data: Vec::new(),
+            searched: 0,
+            done: false,
+        }
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let found = self.data[self.searched..]
+                .windows(self.delim.len())
+                .position(|window| window == &self.delim[..]);
+            if let Some(position) = found {
+                let end = self.searched + position;
+                let mut record: Vec<u8> = self.data.drain(..end + self.delim.len()).collect();
+                record.truncate(end);
+                self.searched = 0;
+                return Ok(Some(record));
+            }
+
+            if self.done {
+                self.searched = 0;
+                return Ok(if self.data.is_empty() {
+                    None
+                } else {
+                    Some(mem::take(&mut self.data))
+                });
+            }
+
+            // A delimiter may start in the unmatched tail, so search that again with more data.
+            self.searched = self.data.len().saturating_sub(self.delim.len() - 1);
+            match self.input.read(&mut self.chunk)? {
+                0 => self.done = true,
+                bytes_read => self.data.extend_from_slice(&self.chunk[..bytes_read]),
+            }
+        }
+    }
+}
+
+impl<Io: IoStrategy> Iterator for SplitOnBytes<Io> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use FileInput;
+    use super::Endian;
+    use test_util::MockIo;
+
+    #[test]
+    fn split_on_bytes_across_sources() {
+        let paths = vec!["testdata/records_a", "testdata/records_b"];
+        let records: Vec<Vec<u8>> =
+            FileInput::new(&paths).split_on_bytes(b";;").map(|r| r.unwrap()).collect();
+
+        assert_eq!(records,
+                   [b"first".to_vec(),
+                    b"second".to_vec(),
+                    b"third".to_vec(),
+                    b"fourth\n".to_vec()]);
+    }
+
+    #[test]
+    fn split_on_bytes_across_reads() {
+        let io = MockIo::new().file("a", b"one\r\n\r\ntwo\r\n\r\n\r\n\r\n").trickle();
+        let records: Vec<Vec<u8>> = FileInput::with_strategy(&["a"], io)
+            .split_on_bytes(b"\r\n\r\n")
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert_eq!(records, [b"one".to_vec(), b"two".to_vec(), b"".to_vec()]);
+    }
+
+    #[test]
+    fn frames_span_sources() {
+        let paths = vec!["testdata/1", "testdata/2"];
+        let frames: Vec<Vec<u8>> = FileInput::new(&paths).frames(4).map(|f| f.unwrap()).collect();
+
+        assert_eq!(frames,
+                   [b"One.".to_vec(), b"\nTwo".to_vec(), b".\nTw".to_vec(), b"o.\n".to_vec()]);
+    }
+
+    #[test]
+    fn frames_within_sources() {
+        let paths = vec!["testdata/1", "testdata/2"];
+        let frames: Vec<Vec<u8>> = FileInput::new(&paths)
+            .frames(4)
+            .within_sources()
+            .map(|f| f.unwrap())
+            .collect();
+
+        assert_eq!(frames,
+                   [b"One.".to_vec(),
+                    b"\n".to_vec(),
+                    b"Two.".to_vec(),
+                    b"\nTwo".to_vec(),
+                    b".\n".to_vec()]);
+    }
+
+    #[test]
+    fn frames_within_sources_exclude_injected_data() {
+        let frames: Vec<Vec<u8>> = FileInput::new(&["testdata/1"])
+            .stream_header(b"<<".to_vec())
+            .stream_footer(b">>".to_vec())
+            .frames(4)
+            .within_sources()
+            .map(|f| f.unwrap())
+            .collect();
+
+        assert_eq!(frames,
+                   [b"<<".to_vec(), b"One.".to_vec(), b"\n".to_vec(), b">>".to_vec()]);
+    }
+
+    #[test]
+    fn length_prefixed_frames() {
+        let paths = vec!["testdata/frames_a", "testdata/frames_b"];
+        let frames: Vec<Vec<u8>> = FileInput::new(&paths)
+            .length_prefixed_frames(Endian::Big)
+            .map(|f| f.unwrap())
+            .collect();
+
+        assert_eq!(frames, [b"abc".to_vec(), b"hi".to_vec()]);
+    }
+
+    #[test]
+    fn length_prefixed_frames_little_endian() {
+        let io = MockIo::new().file("a", b"\x01\x00\x00\x00a\x00\x00\x00\x00");
+        let frames: Vec<Vec<u8>> = FileInput::with_strategy(&["a"], io)
+            .length_prefixed_frames(Endian::Little)
+            .map(|f| f.unwrap())
+            .collect();
+
+        assert_eq!(frames, [b"a".to_vec(), Vec::new()]);
+    }
+
+    #[test]
+    fn length_prefixed_frames_truncated() {
+        let io = MockIo::new().file("a", b"\x00\x00\x00\x05abc");
+        let mut frames = FileInput::with_strategy(&["a"], io).length_prefixed_frames(Endian::Big);
+
+        assert_eq!(frames.next().unwrap().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,146 @@
+//! A harness that drives the read loop with randomized sources and readers, for fuzzing.
+//!
+//! The fuzz target in `fuzz/` calls `run` with arbitrary bytes when built with `cfg(fuzzing)`;
+//! the tests below call it with deterministic pseudo-random bytes.
+use std::collections::HashMap;
+use std::io;
+use std::io::{Cursor, ErrorKind, Read};
+
+use FileInput;
+use strategy::IoStrategy;
+use test_util::{Chunked, FailingIoStream};
+
+
+/// Reads the bytes driving a run, as zeros once they're exhausted.
+struct Unstructured<'a>(&'a [u8]);
+
+impl<'a> Unstructured<'a> {
+    fn next(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(byte)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.next().unwrap_or(0) as usize % bound
+    }
+}
+
+enum FuzzFile {
+    Data(Vec<u8>, usize),
+    Failing(Vec<u8>, usize),
+}
+
+/// Serves files that yield their data a few bytes at a time, some of them failing at the end.
+#[derive(Default)]
+struct FuzzIo(HashMap<String, FuzzFile>);
+
+impl IoStrategy for FuzzIo {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        match self.0.get(path) {
+            Some(FuzzFile::Data(data, max_read)) => {
+                Ok(Box::new(Chunked(Cursor::new(data.clone()), *max_read)))
+            }
+            Some(FuzzFile::Failing(data, max_read)) => {
+                Ok(Box::new(Chunked(FailingIoStream::new(data, ErrorKind::Other), *max_read)))
+            }
+            None => Err(io::Error::new(ErrorKind::NotFound, "no such fuzz file")),
+        }
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::empty()))
+    }
+}
+
+/// Builds sources and a sequence of reads from `data`, reads them, and panics if the result
+/// differs from what the sources contain.
+///
+/// Sources may be empty, missing, or fail after their data, and reads may be given buffers
+/// of any length, including zero. Missing sources are skipped after their error; reading stops
+/// at the first failing source.
+pub fn run(data: &[u8]) {
+    let mut input = Unstructured(data);
+    let mut io = FuzzIo::default();
+    let mut paths = Vec::new();
+    let mut expected = Vec::new();
+    let mut expect_error = false;
+
+    for i in 0..input.below(8) {
+        let path = i.to_string();
+        let len = input.below(32);
+        let contents: Vec<u8> = (0..len).map(|_| input.next().unwrap_or(0)).collect();
+        let max_read = input.below(8) + 1;
+        match input.below(4) {
+            0 => {}
+            1 => {
+                if !expect_error {
+                    expected.extend_from_slice(&contents);
+                    expect_error = true;
+                }
+                io.0.insert(path.clone(), FuzzFile::Failing(contents, max_read));
+            }
+            _ => {
+                if !expect_error {
+                    expected.extend_from_slice(&contents);
+                }
+                io.0.insert(path.clone(), FuzzFile::Data(contents, max_read));
+            }
+        }
+        paths.push(path);
+    }
+
+    let mut fileinput = FileInput::with_strategy(&paths, io);
+    let mut output = Vec::new();
+    let mut buf = [0; 16];
+    let errored = loop {
+        let len = input.next().map_or(buf.len(), |byte| byte as usize % (buf.len() + 1));
+        match fileinput.read(&mut buf[..len]) {
+            Ok(0) if len == 0 => {}
+            Ok(0) => break false,
+            Ok(bytes_read) => {
+                assert!(bytes_read <= len);
+                output.extend_from_slice(&buf[..bytes_read]);
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+            Err(_) => break true,
+        }
+    };
+
+    assert_eq!(output, expected);
+    assert_eq!(errored, expect_error);
+    if !errored {
+        assert_eq!(fileinput.read(&mut buf).unwrap(), 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::run;
+
+    #[test]
+    fn randomized_read_loop() {
+        // A fixed xorshift sequence, so failures reproduce.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..500 {
+            let data: Vec<u8> = (0..128)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect();
+            run(&data);
+        }
+    }
+
+    #[test]
+    fn seed_corpus() {
+        for entry in fs::read_dir("fuzz/corpus/read_loop").unwrap() {
+            run(&fs::read(entry.unwrap().path()).unwrap());
+        }
+    }
+}
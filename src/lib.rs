@@ -17,22 +17,168 @@
 //!     println!("{}", line.unwrap());
 //! }
 //! ```
-use std::fs::File;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::env;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
 use std::io;
-use std::io::{Read, stdin};
-use std::borrow::Borrow;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::borrow::{Borrow, Cow};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "age")]
+extern crate age;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "tempfile")]
+extern crate tempfile;
+#[cfg(feature = "http")]
+extern crate ureq;
+#[cfg(feature = "zip")]
+extern crate zip;
+
+mod adapters;
+mod chunks;
+mod csv;
+mod frames;
+#[cfg(any(test, fuzzing))]
+#[doc(hidden)]
+pub mod fuzz;
+mod lines;
+mod stats;
+mod strategy;
+#[cfg(any(test, fuzzing))]
+#[cfg_attr(fuzzing, allow(dead_code))]
+mod test_util;
+mod unordered;
+
+pub use adapters::{FilterLines, MapBytes, Meter, Metered, PrefixSource, SqueezeBlankLines,
+                   Tee};
+pub use chunks::{Chunks, Documents, SourceReaders};
+pub use csv::CsvRecords;
+pub use frames::{Endian, Frames, LengthPrefixedFrames, SplitOnBytes};
+pub use lines::{ByteLines, Line, PairLines, RobustLine, RobustLines};
+pub use stats::LineLengthStats;
+pub use strategy::{CodecRegistry, FileKind, FileSystem, IoCodec, IoDefault, IoMemory, IoStrategy,
+                   IoVfs, Metadata, ReadAny, ReadSeek};
+#[cfg(feature = "age")]
+pub use strategy::IoAge;
+#[cfg(target_os = "linux")]
+pub use strategy::IoDirect;
+#[cfg(feature = "gzip")]
+pub use strategy::IoGzip;
+#[cfg(feature = "http")]
+pub use strategy::IoHttp;
+#[cfg(feature = "zip")]
+pub use strategy::IoZip;
+#[cfg(feature = "tempfile")]
+pub use tempfile::TempPath;
+pub use unordered::Unordered;
 
 
 /// A file source.
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum Source {
     /// Read from the process's standard in.
     Stdin,
     /// Read from the specified file.
     File(String),
+    /// Read from the process's standard in, reported under the given name.
+    NamedStdin(String),
+    /// Read at most `length` bytes from the specified file, starting `offset` bytes in.
+    FileRange {
+        /// The path of the file.
+        path: String,
+        /// Where in the file to start reading.
+        offset: u64,
+        /// The most bytes to read.
+        length: u64,
+    },
+    /// Stop reading; no sources after this one are read.
+    Stop,
+    /// Data added by `FileInput` itself rather than read from a source, such as a
+    /// `stream_header`. This is only used to attribute data and is never queued.
+    Injected,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::Stdin => write!(f, "<stdin>"),
+            Source::File(ref path) => write!(f, "{}", path),
+            Source::NamedStdin(ref name) => write!(f, "<stdin: {}>", name),
+            Source::FileRange { ref path, offset, length } => {
+                write!(f, "{}[{}..{}]", path, offset, offset.saturating_add(length))
+            }
+            Source::Stop => write!(f, "<stop>"),
+            Source::Injected => write!(f, "<injected>"),
+        }
+    }
+}
+
+impl Source {
+    /// Returns whether this source reads from standard in, named or not.
+    pub fn is_stdin(&self) -> bool {
+        matches!(*self, Source::Stdin | Source::NamedStdin(_))
+    }
+
+    /// Returns the path of the file this source reads from, if it's a file or file range.
+    pub fn path(&self) -> Option<&str> {
+        match *self {
+            Source::File(ref path) | Source::FileRange { ref path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns how many bytes this source reads from a file of `file_len` bytes.
+    fn len_within(&self, file_len: u64) -> u64 {
+        match *self {
+            Source::FileRange { offset, length, .. } => file_len.saturating_sub(offset).min(length),
+            _ => file_len,
+        }
+    }
+}
+
+/// Opens the part of the file at `path` read by a `Source::FileRange`.
+fn open_range<Io: IoStrategy>(io: &Io,
+                              path: &str,
+                              offset: u64,
+                              length: u64)
+                              -> io::Result<Box<dyn ReadAny + Send>> {
+    let mut reader = io.open_seekable(path)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "source is not seekable"))?;
+    reader.seek(SeekFrom::Start(offset))?;
+    Ok(Box::new(reader.take(length)))
+}
+
+/// Adds the name of `source` to the message of `error`, preserving its kind.
+fn source_error(source: &Source, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", source, error))
+}
+
+/// Decrements `remaining` for each line ending in `data`, returning how much of `data` to keep
+/// so that it ends with the line which brings `remaining` to zero.
+fn count_down_lines(data: &[u8], remaining: &mut usize) -> usize {
+    for (i, _) in data.iter().enumerate().filter(|&(_, &b)| b == b'\n') {
+        *remaining -= 1;
+        if *remaining == 0 {
+            return i + 1;
+        }
+    }
+    data.len()
 }
 
-fn make_source_vec<T>(filenames: &[T]) -> Vec<Source>
+fn make_source_vec<T>(filenames: &[T], dash_is_stdin: bool) -> Vec<Source>
     where T: Borrow<str>
 {
     if filenames.is_empty() {
@@ -42,22 +188,181 @@ fn make_source_vec<T>(filenames: &[T]) -> Vec<Source>
     let mut sources = Vec::with_capacity(filenames.len());
     for filename in filenames {
         sources.push(match filename.borrow() {
-            "-" => Source::Stdin,
+            "-" if dash_is_stdin => Source::Stdin,
             filename => Source::File(filename.to_string()),
         });
     }
     sources
 }
 
+/// A saved copy of the sources remaining in a `FileInput`'s queue.
+///
+/// This struct is created by `FileInput::save_state`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceQueueSnapshot {
+    sources: Vec<Source>,
+}
+
+impl SourceQueueSnapshot {
+    /// Returns the sources that were queued when the snapshot was taken.
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+}
+
+/// The case `FileInput::ascii_case` converts ASCII letters to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    /// Convert `a` through `z` to `A` through `Z`.
+    Upper,
+    /// Convert `A` through `Z` to `a` through `z`.
+    Lower,
+}
+
+/// The line terminator used in content that `FileInput` adds to the input, set with
+/// `FileInput::newline_style`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NewlineStyle {
+    /// `\n`, as on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as on Windows.
+    CrLf,
+}
+
+/// Converts bare `\n`s in `data` to `\r\n`.
+fn to_crlf(data: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(data.len());
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+    }
+    converted
+}
+
+/// What a source held when it was classified by `FileInput::classify_sources`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceStatus {
+    /// The file does not exist.
+    Missing,
+    /// The file exists but is empty.
+    Empty,
+    /// The file exists and holds this many bytes.
+    NonEmpty(u64),
+    /// The source is standard input, whose size can't be known in advance.
+    Stdin,
+}
+
+/// The byte ranges of some combined data that came from each source, in order.
+pub type SourceMap = Vec<(Source, Range<usize>)>;
+
+type OpenCallback = Box<dyn FnMut(&Source) + Send>;
+type InspectCallback = Box<dyn FnMut(&Source, &[u8]) + Send>;
+type SeekCallback = Box<dyn Fn(&Source, &mut dyn Seek) -> io::Result<u64> + Send>;
+/// A source's reader, or `None` if the source was opened but shouldn't be read.
+type Opened = io::Result<Option<Box<dyn ReadAny + Send>>>;
+
 struct State {
     source: Source,
-    reader: Box<Read>,
+    reader: Box<dyn ReadAny + Send>,
+    announced: bool,
+    delivered: u64,
+    binary: bool,
+    stdin_retries: usize,
+    line: usize,
+    column: usize,
+    head_remaining: Option<usize>,
+    rechecked_at: Option<u64>,
+    seekable: bool,
+}
+
+/// The default for `FileInput::read_chunk_size`.
+const DEFAULT_CHUNK_SIZE: usize = 8192;
+
+/// The most `read_to_end` reserves up front from a size hint, since sizes can overstate what
+/// will be read, e.g. for sparse files or with `max_lines`.
+const MAX_RESERVATION: u64 = 64 << 20;
+
+/// The UTF-8 byte order mark removed by `strip_leading_bom`.
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Returns the name of the encoding indicated by the byte order mark at the start of `data`,
+/// if there is one.
+fn bom_encoding(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(UTF8_BOM) {
+        Some("UTF-8")
+    } else if data.starts_with(b"\xff\xfe") {
+        Some("UTF-16LE")
+    } else if data.starts_with(b"\xfe\xff") {
+        Some("UTF-16BE")
+    } else {
+        None
+    }
 }
 
+/// How much of each source `skip_binary` inspects for NUL bytes.
+const BINARY_PEEK_LEN: u64 = 8000;
+
 /// A wrapper which reads from multiple streams.
-pub struct FileInput {
+///
+/// A `FileInput` is `Send` whenever its `IoStrategy` is, so it can be moved to a worker thread.
+pub struct FileInput<Io: IoStrategy = IoDefault> {
+    io: Io,
     sources: Vec<Source>,
+    unqueued_paths: Option<Vec<String>>,
+    dash_is_stdin: bool,
     state: Option<State>,
+    on_open: Option<OpenCallback>,
+    inspect: Option<InspectCallback>,
+    on_open_seek: Option<SeekCallback>,
+    require_seekable: bool,
+    report_empty_files: bool,
+    stop_at_empty_file: bool,
+    recheck_size_at_eof: bool,
+    expected_sizes: HashMap<String, u64>,
+    buffer_stdin: bool,
+    skip_binary: bool,
+    require_consistent_bom: bool,
+    first_bom: Option<Option<&'static str>>,
+    lines_remaining: Option<usize>,
+    head_per_source: Option<usize>,
+    stdin_retry_attempts: usize,
+    stdin_retry_delay: Duration,
+    cancel: Option<Arc<AtomicBool>>,
+    cancel_as_error: bool,
+    paused: bool,
+    deadline: Option<Instant>,
+    prevalidate_next: bool,
+    pending_error: Option<io::Error>,
+    chunk_size: usize,
+    prefetch_memory_budget: Option<usize>,
+    #[cfg(unix)]
+    nonblocking_fifo: bool,
+    recursive: bool,
+    follow_symlinks: bool,
+    visited_dirs: HashSet<String>,
+    stdin_buffer: Option<Arc<[u8]>>,
+    opened: usize,
+    metadata_cache: RefCell<HashMap<String, Metadata>>,
+    ascii_case: Option<Case>,
+    total_delivered: usize,
+    leading_bom_pending: bool,
+    boundaries: Vec<(usize, Source)>,
+    line_cursor: lines::LineCursor,
+    include_incomplete_final_line: bool,
+    stream_header: Cursor<Vec<u8>>,
+    stream_footer: Cursor<Vec<u8>>,
+    newline_style: NewlineStyle,
+    injected_styled: bool,
+    header_pending: bool,
+    header: Vec<u8>,
+    missing_placeholder: Option<Vec<u8>>,
+    skip_duplicate_content: bool,
+    last_content: Option<Vec<u8>>,
+    opened_seekable: bool,
+    leading_bom_stripped: bool,
 }
 
 impl FileInput {
@@ -65,140 +370,3208 @@ impl FileInput {
     pub fn new<T>(paths: &[T]) -> Self
         where T: Borrow<str>
     {
-        FileInput {
-            sources: make_source_vec(paths),
-            state: None,
-        }
+        FileInput::with_strategy(paths, IoDefault)
     }
 
-    /// Returns the current source being read from.
+    /// Constructs a new `FileInput` that will read a range of bytes from each file in
+    /// `manifest`, in order.
     ///
-    /// This function will return `None` if no reading has been done yet or all the inputs have
-    /// been drained.
-    pub fn source(&self) -> Option<Source> {
-        self.state.as_ref().map(|s| s.source.clone())
+    /// Each entry is a `(path, offset, length)` and becomes a `Source::FileRange`: the file is
+    /// opened, seeked to `offset`, and at most `length` bytes are read from it. The same file
+    /// may appear more than once. Every path is a file, so `-` doesn't mean standard input here.
+    pub fn from_manifest(manifest: &[(String, u64, u64)]) -> Self {
+        let sources = manifest.iter()
+            .map(|&(ref path, offset, length)| {
+                Source::FileRange {
+                    path: path.clone(),
+                    offset,
+                    length,
+                }
+            })
+            .collect();
+        FileInput::from_sources(sources, IoDefault)
     }
 
-    fn open_next_file(&mut self) -> io::Result<()> {
-        let next_source = self.sources.remove(0);
-        let reader: Box<Read> = match &next_source {
-            &Source::Stdin => Box::new(stdin()),
-            &Source::File(ref path) => Box::new(try!(File::open(path))),
-        };
+    /// Constructs a new `FileInput` that will read from exactly two files, for use with
+    /// `pair_lines`.
+    pub fn pair(a: &str, b: &str) -> Self {
+        FileInput::new(&[a, b])
+    }
 
-        self.state = Some(State {
-            source: next_source,
-            reader: reader,
-        });
+    /// Constructs a new `FileInput` that will read from the whitespace-separated paths in the
+    /// environment variable `var_name`.
+    ///
+    /// Standard input is read if the variable is unset, empty, or not valid unicode.
+    pub fn from_env(var_name: &str) -> Self {
+        let paths = env::var(var_name).unwrap_or_default();
+        paths.split_whitespace().collect()
+    }
+}
 
-        Ok(())
+impl<S: Into<String>> FromIterator<S> for FileInput {
+    /// Constructs a new `FileInput` that will read from the paths yielded by `iter`, exactly as
+    /// `FileInput::new` would.
+    fn from_iter<I>(iter: I) -> Self
+        where I: IntoIterator<Item = S>
+    {
+        let paths: Vec<String> = iter.into_iter().map(Into::into).collect();
+        FileInput::new(&paths)
     }
 }
 
-impl Read for FileInput {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        loop {
-            if self.state.is_none() {
-                if self.sources.is_empty() {
-                    return Ok(0);
+impl FileInput<IoMemory> {
+    /// Constructs a new `FileInput` that will read from named in-memory buffers, in order.
+    ///
+    /// Each buffer becomes a `Source::File` with the given name, which makes this convenient
+    /// for testing code built on `FileInput` without touching the filesystem.
+    pub fn from_pairs(pairs: Vec<(String, Vec<u8>)>) -> Self {
+        let mut io = IoMemory::new();
+        let mut sources = Vec::with_capacity(pairs.len());
+        for (name, data) in pairs {
+            io.insert(name.clone(), data);
+            sources.push(Source::File(name));
+        }
+
+        FileInput::from_sources(sources, io)
+    }
+}
+
+impl<Io: IoStrategy> FileInput<Io> {
+    /// Constructs a new `FileInput` that will open the files specified using `io`.
+    pub fn with_strategy<T>(paths: &[T], io: Io) -> Self
+        where T: Borrow<str>
+    {
+        let mut fileinput = FileInput::from_sources(Vec::new(), io);
+        let paths = paths.iter().map(|path| path.borrow().to_string()).collect();
+        fileinput.unqueued_paths = Some(paths);
+        fileinput
+    }
+
+    fn from_sources(sources: Vec<Source>, io: Io) -> Self {
+        FileInput {
+            io,
+            sources,
+            unqueued_paths: None,
+            dash_is_stdin: true,
+            state: None,
+            on_open: None,
+            inspect: None,
+            on_open_seek: None,
+            require_seekable: false,
+            report_empty_files: false,
+            stop_at_empty_file: false,
+            recheck_size_at_eof: false,
+            expected_sizes: HashMap::new(),
+            buffer_stdin: false,
+            skip_binary: false,
+            require_consistent_bom: false,
+            first_bom: None,
+            lines_remaining: None,
+            head_per_source: None,
+            stdin_retry_attempts: 0,
+            stdin_retry_delay: Duration::from_millis(0),
+            cancel: None,
+            cancel_as_error: false,
+            paused: false,
+            deadline: None,
+            prevalidate_next: false,
+            pending_error: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            prefetch_memory_budget: None,
+            #[cfg(unix)]
+            nonblocking_fifo: false,
+            recursive: false,
+            follow_symlinks: false,
+            visited_dirs: HashSet::new(),
+            stdin_buffer: None,
+            opened: 0,
+            metadata_cache: RefCell::new(HashMap::new()),
+            ascii_case: None,
+            total_delivered: 0,
+            leading_bom_pending: false,
+            boundaries: Vec::new(),
+            line_cursor: lines::LineCursor::default(),
+            include_incomplete_final_line: true,
+            stream_header: Cursor::new(Vec::new()),
+            stream_footer: Cursor::new(Vec::new()),
+            newline_style: NewlineStyle::Lf,
+            injected_styled: false,
+            header_pending: false,
+            header: Vec::new(),
+            missing_placeholder: None,
+            skip_duplicate_content: false,
+            last_content: None,
+            opened_seekable: false,
+            leading_bom_stripped: false,
+        }
+    }
+
+    /// Adds a source to the end of the queue.
+    pub fn push_source(mut self, source: Source) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Adds standard input to the end of the queue, reported by `source` and in errors under
+    /// `name`.
+    ///
+    /// This tells apart several readings of standard input, which otherwise all look alike.
+    pub fn push_stdin_named<S: Into<String>>(self, name: S) -> Self {
+        self.push_source(Source::NamedStdin(name.into()))
+    }
+
+    /// Adds a `Source::Stop` to the end of the queue, ending the input there.
+    pub fn push_stop(self) -> Self {
+        self.push_source(Source::Stop)
+    }
+
+    /// Rewrites the path of every queued `Source::File` and `Source::FileRange` with `f`.
+    ///
+    /// Returning `Some(path)` replaces the path and `None` drops the source. Other sources are
+    /// left alone.
+    pub fn rewrite_paths<F>(mut self, f: F) -> Self
+        where F: Fn(&str) -> Option<String>
+    {
+        self.queue_paths();
+        self.sources = self.sources
+            .into_iter()
+            .filter_map(|source| match source {
+                Source::File(path) => f(&path).map(Source::File),
+                Source::FileRange { path, offset, length } => {
+                    f(&path).map(|path| {
+                        Source::FileRange {
+                            path,
+                            offset,
+                            length,
+                        }
+                    })
                 }
+                other => Some(other),
+            })
+            .collect();
+        self
+    }
 
-                try!(self.open_next_file());
-            }
+    /// Controls whether `-` in the paths given at construction means standard input (the
+    /// default) or a file literally named `-`.
+    ///
+    /// The paths are queued with this setting when the queue is first read or changed by
+    /// anything other than `push_source` and its shorthands, so it must be set before options
+    /// such as `rewrite_paths` and `prepend_stdin_if_piped`. The standard input read when no
+    /// paths are given at all, and the sources of constructors that don't take paths, such as
+    /// `from_manifest` and `from_pairs`, are left alone.
+    pub fn dash_is_stdin(mut self, enabled: bool) -> Self {
+        self.dash_is_stdin = enabled;
+        self
+    }
 
-            let bytes_read = try!(self.state.as_mut().unwrap().reader.read(buf));
+    /// Adds the paths given at construction to the front of the queue, if they're not queued
+    /// yet.
+    fn queue_paths(&mut self) {
+        if let Some(paths) = self.unqueued_paths.take() {
+            let queued = make_source_vec(&paths, self.dash_is_stdin);
+            let pushed = mem::replace(&mut self.sources, queued);
+            self.sources.extend(pushed);
+        }
+    }
 
-            if bytes_read == 0 {
-                self.state = None;
-                continue;
+    /// Returns the queue as `queue_paths` would leave it, without changing it.
+    fn queued(&self) -> Cow<'_, [Source]> {
+        match self.unqueued_paths {
+            Some(ref paths) => {
+                let mut sources = make_source_vec(paths, self.dash_is_stdin);
+                sources.extend_from_slice(&self.sources);
+                Cow::Owned(sources)
             }
-
-            return Ok(bytes_read);
+            None => Cow::Borrowed(&self.sources),
         }
     }
-}
 
-#[cfg(test)]
-mod test {
-    mod source_vec {
-        use super::super::{make_source_vec, Source};
+    /// Keeps only the queued sources whose index is in `start..end`, for splitting a list of
+    /// inputs between workers.
+    ///
+    /// Indices past the end of the queue are clamped.
+    pub fn source_range(mut self, start: usize, end: usize) -> Self {
+        self.queue_paths();
+        let end = end.min(self.sources.len());
+        let start = start.min(end);
+        self.sources.truncate(end);
+        self.sources.drain(..start);
+        self
+    }
 
-        #[test]
-        fn empty_list_makes_stdin() {
-            let names: Vec<String> = vec![];
-            let paths = make_source_vec(&names);
-            assert_eq!(paths, [Source::Stdin]);
-        }
+    /// Keeps only the queued sources whose index modulo `shard_count` is `shard_index`, for
+    /// splitting a list of inputs between `shard_count` workers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shard_count` is zero.
+    pub fn shard(mut self, shard_index: usize, shard_count: usize) -> Self {
+        assert!(shard_count > 0, "shard count must be non-zero");
+        self.queue_paths();
+        self.sources = self.sources
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, _)| i % shard_count == shard_index)
+            .map(|(_, source)| source)
+            .collect();
+        self
+    }
 
-        #[test]
-        fn dash_makes_stdin() {
-            let names = vec!["-"];
-            let paths = make_source_vec(&names);
-            assert_eq!(paths, [Source::Stdin]);
+    /// Removes queued files that haven't been modified since `time`, leaving standard input
+    /// and files modified after it.
+    ///
+    /// Modification times are queried through the `IoStrategy`, and failing to query one is an
+    /// error.
+    pub fn modified_since(mut self, time: SystemTime) -> io::Result<Self> {
+        self.queue_paths();
+        let mut kept = Vec::with_capacity(self.sources.len());
+        for source in mem::take(&mut self.sources) {
+            if let Some(path) = source.path() {
+                let modified = self.metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .map_err(|e| source_error(&source, e))?;
+                if modified <= time {
+                    continue;
+                }
+            }
+            kept.push(source);
         }
+        self.sources = kept;
+        Ok(self)
+    }
 
-        #[test]
-        fn filename_makes_path() {
-            let names = vec!["example-file"];
-            let paths = make_source_vec(&names);
-            assert_eq!(paths, [Source::File("example-file".to_string())]);
+    /// Reorders the queued sources by size, smallest first if `ascending` and largest first
+    /// otherwise.
+    ///
+    /// Standard input always sorts last, and sources of equal size keep their order. Only the
+    /// sources before any `Source::Stop` are reordered. Failing to query a file's size is an
+    /// error.
+    pub fn order_by_size(mut self, ascending: bool) -> io::Result<Self> {
+        self.queue_paths();
+        let end = self.sources.iter().position(|s| *s == Source::Stop);
+        let tail = self.sources.split_off(end.unwrap_or(self.sources.len()));
+        let mut sized = Vec::with_capacity(self.sources.len());
+        for source in mem::take(&mut self.sources) {
+            let size = match source.path() {
+                Some(path) => {
+                    let metadata = self.metadata(path).map_err(|e| source_error(&source, e))?;
+                    let len = source.len_within(metadata.len());
+                    Some(if ascending { len } else { u64::MAX - len })
+                }
+                None => None,
+            };
+            sized.push((size, source));
         }
 
-        #[test]
-        fn mixed() {
-            let names = vec!["one", "two", "-", "three"];
-            let paths = make_source_vec(&names);
-            assert_eq!(paths,
-                       [Source::File("one".to_string()),
-                        Source::File("two".to_string()),
-                        Source::Stdin,
-                        Source::File("three".to_string())]);
+        sized.sort_by_key(|&(size, _)| (size.is_none(), size));
+        self.sources = sized.into_iter().map(|(_, source)| source).chain(tail).collect();
+        Ok(self)
+    }
+
+    /// Reads standard input before the other sources if it's piped rather than a terminal.
+    ///
+    /// Nothing is added if standard input is already queued.
+    pub fn prepend_stdin_if_piped(mut self) -> Self {
+        self.queue_paths();
+        let queued = self.sources.iter().any(Source::is_stdin);
+        if !queued && !self.io.stdin_is_terminal() {
+            self.sources.insert(0, Source::Stdin);
         }
+        self
     }
 
-    mod fileinput {
-        use super::super::*;
-        use std::io::{Read, ErrorKind, BufRead, BufReader};
+    /// Reads the files in directory sources, recursing into subdirectories.
+    ///
+    /// Directories are expanded in sorted order when they're reached. Each directory is only
+    /// read once, even if it's reachable through several paths, so symlink cycles are harmless.
+    pub fn recursive(mut self) -> Self {
+        self.recursive = true;
+        self
+    }
 
-        #[test]
-        fn read_files() {
-            let paths = vec!["testdata/1", "testdata/2"];
-            let mut fileinput = FileInput::new(&paths);
-            let mut buffer = String::new();
+    /// Controls whether `recursive` descends into symlinked directories. The default is not to.
+    ///
+    /// Directories given as sources are always read, even if they're symlinks.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
 
-            fileinput.read_to_string(&mut buffer).unwrap();
+    /// Registers a callback to be invoked each time a new source becomes current.
+    ///
+    /// By default this happens when a source first yields data, so empty sources are never
+    /// announced. See `report_empty_files` to change that.
+    pub fn on_open<F>(mut self, f: F) -> Self
+        where F: FnMut(&Source) + Send + 'static
+    {
+        self.on_open = Some(Box::new(f));
+        self
+    }
 
-            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
-        }
+    /// Registers a callback to be invoked with the current source and the bytes returned by
+    /// every `read`, just before they're returned.
+    pub fn inspect<F>(mut self, f: F) -> Self
+        where F: FnMut(&Source, &[u8]) + Send + 'static
+    {
+        self.inspect = Some(Box::new(f));
+        self
+    }
 
-        #[test]
-        fn skip_empty_file() {
-            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
-            let mut fileinput = FileInput::new(&paths);
-            let mut buffer = String::new();
+    /// Calls `f` with each file and a seekable reader over it as soon as it's opened, so that
+    /// `f` can position the reader before any data is read.
+    ///
+    /// Files are opened with `IoStrategy::open_seekable`. Sources that can't seek, including
+    /// standard input, are read from the start without calling `f`, unless `require_seekable`
+    /// is used.
+    pub fn on_open_seek<F>(mut self, f: F) -> Self
+        where F: Fn(&Source, &mut dyn Seek) -> io::Result<u64> + Send + 'static
+    {
+        self.on_open_seek = Some(Box::new(f));
+        self
+    }
 
-            fileinput.read_to_string(&mut buffer).unwrap();
+    /// Makes sources that can't seek an `ErrorKind::Unsupported` error when `on_open_seek` is
+    /// used.
+    pub fn require_seekable(mut self) -> Self {
+        self.require_seekable = true;
+        self
+    }
 
-            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
-        }
+    /// Announces every source as soon as it is opened, even if it turns out to be empty.
+    ///
+    /// Without this, empty sources are skipped over silently and never passed to the `on_open`
+    /// callback.
+    pub fn report_empty_files(mut self) -> Self {
+        self.report_empty_files = true;
+        self
+    }
 
-        #[test]
-        fn get_source() {
-            let paths = vec!["testdata/1", "testdata/2"];
-            let fileinput = FileInput::new(&paths);
-            let mut reader = BufReader::new(fileinput);
-            let mut buffer = String::new();
+    /// Ends the input at the first source that turns out to be empty.
+    ///
+    /// By default empty sources are skipped and reading continues with the next source.
+    pub fn stop_at_empty_file(mut self) -> Self {
+        self.stop_at_empty_file = true;
+        self
+    }
 
-            assert_eq!(reader.get_ref().source(), None);
-            reader.read_line(&mut buffer).unwrap();
-            assert_eq!(reader.get_ref().source(),
-                       Some(Source::File("testdata/1".to_string())));
-            reader.read_line(&mut buffer).unwrap();
-            assert_eq!(reader.get_ref().source(),
-                       Some(Source::File("testdata/2".to_string())));
-            reader.read_line(&mut buffer).unwrap();
-            reader.read_line(&mut buffer).unwrap();
-            assert_eq!(reader.get_ref().source(), None);
+    /// Checks the size of each file when its end is reached, and carries on reading if it has
+    /// grown, rather than moving on to the next source.
+    ///
+    /// This catches data appended to a file while it's being read. Sizes are compared with the
+    /// number of bytes read, so this is only meaningful for strategies that don't transform
+    /// file contents.
+    pub fn recheck_size_at_eof(mut self) -> Self {
+        self.recheck_size_at_eof = true;
+        self
+    }
+
+    /// Checks that each file named in `sizes` delivers exactly the given number of bytes.
+    ///
+    /// When such a file ends having delivered a different number of bytes, `read` returns an
+    /// `ErrorKind::InvalidData` error naming it, and reading can carry on with the next source.
+    /// Sizes are compared with the bytes delivered, so options that transform or shorten the
+    /// input affect them.
+    pub fn expect_source_size(mut self, sizes: HashMap<String, u64>) -> Self {
+        self.expected_sizes = sizes;
+        self
+    }
+
+    /// Reads standard input fully into memory the first time it's needed, and replays that
+    /// buffer for every `Source::Stdin`.
+    ///
+    /// Without this, only the first `Source::Stdin` yields any data. Note that the whole of
+    /// standard input is held in memory for the lifetime of this `FileInput`.
+    pub fn buffer_stdin(mut self) -> Self {
+        self.buffer_stdin = true;
+        self
+    }
+
+    /// Skips sources that look like binary files, like grep's `-I`.
+    ///
+    /// A source is considered binary if there's a NUL byte in its first 8000 bytes, which are
+    /// inspected before any of its data is delivered.
+    pub fn skip_binary(mut self) -> Self {
+        self.skip_binary = true;
+        self
+    }
+
+    /// Skips a source whose content is identical to that of the source delivered just before
+    /// it.
+    ///
+    /// Each source is read in full and compared byte for byte before any of its data is
+    /// delivered, so this holds two whole sources in memory at a time: the one being compared
+    /// and the one before it.
+    pub fn skip_duplicate_content(mut self) -> Self {
+        self.skip_duplicate_content = true;
+        self
+    }
+
+    /// Delivers only the first `n` lines of each source, like `head -n` over several files.
+    ///
+    /// Each `n`th line is delivered in full, including its terminator.
+    pub fn head_per_source(mut self, n: usize) -> Self {
+        self.head_per_source = Some(n);
+        self
+    }
+
+    /// Delivers `bytes` in place of any source that doesn't exist, instead of returning an
+    /// `ErrorKind::NotFound` error.
+    ///
+    /// The placeholder is delivered as the missing source's data, so `source` and `boundaries`
+    /// still report it, keeping positions aligned with the list of sources.
+    pub fn missing_source_placeholder(mut self, bytes: Vec<u8>) -> Self {
+        self.missing_placeholder = Some(bytes);
+        self
+    }
+
+    /// Treats the first source as a header, such as a schema for the sources after it.
+    ///
+    /// The header is read in full when the first data is read and kept for `header_bytes`
+    /// instead of being delivered, so the combined input consists of the remaining sources.
+    pub fn header_source(mut self) -> Self {
+        self.header_pending = true;
+        self
+    }
+
+    /// Removes a UTF-8 byte order mark from the very start of the combined input.
+    ///
+    /// Only the first bytes delivered are checked; byte order marks at the start of later
+    /// sources are left alone. A stripped byte order mark still counts as read from its source
+    /// for `expect_source_size` and `recheck_size_at_eof`.
+    pub fn strip_leading_bom(mut self) -> Self {
+        self.leading_bom_pending = true;
+        self
+    }
+
+    /// Controls whether line iterators yield a final line that has no terminator. The default
+    /// is to yield it.
+    ///
+    /// This affects `robust_lines`, `next_line`, and `tail`, and only the last line of the
+    /// whole input; other unterminated lines, such as at the end of a source read by
+    /// `robust_lines`, are always yielded.
+    pub fn include_incomplete_final_line(mut self, include: bool) -> Self {
+        self.include_incomplete_final_line = include;
+        self
+    }
+
+    /// Delivers `header` before any data from the sources.
+    ///
+    /// APIs that attribute data to sources, such as `chunks` and `next_line`, attribute the
+    /// header to `Source::Injected`.
+    pub fn stream_header(mut self, header: Vec<u8>) -> Self {
+        self.stream_header = Cursor::new(header);
+        self
+    }
+
+    /// Delivers `footer` once all the sources have been read, or `max_lines` has been reached.
+    ///
+    /// The footer isn't delivered if reading is cancelled. Like the header, it's attributed to
+    /// `Source::Injected`.
+    pub fn stream_footer(mut self, footer: Vec<u8>) -> Self {
+        self.stream_footer = Cursor::new(footer);
+        self
+    }
+
+    /// Sets the line terminator used in content added to the input, such as `stream_header`,
+    /// `stream_footer` and `missing_source_placeholder`. The default is `NewlineStyle::Lf`.
+    ///
+    /// With `NewlineStyle::CrLf`, each `\n` in added content not already preceded by `\r` is
+    /// delivered as `\r\n`. Data read from sources is never altered.
+    pub fn newline_style(mut self, style: NewlineStyle) -> Self {
+        self.newline_style = style;
+        self
+    }
+
+    /// Converts the ASCII letters in the input to `case`, leaving all other bytes untouched.
+    pub fn ascii_case(mut self, case: Case) -> Self {
+        self.ascii_case = Some(case);
+        self
+    }
+
+    /// Checks that every non-empty source starts with the same byte order mark as the first,
+    /// or that none of them has one.
+    ///
+    /// UTF-8 and UTF-16 byte order marks are recognized. A source that doesn't match is an
+    /// `ErrorKind::InvalidData` error, and reading can carry on with the next source.
+    pub fn require_consistent_bom(mut self) -> Self {
+        self.require_consistent_bom = true;
+        self
+    }
+
+    /// Ends the input after `n` lines have been delivered.
+    ///
+    /// The `n`th line is delivered in full, including its terminator.
+    pub fn max_lines(mut self, n: usize) -> Self {
+        self.lines_remaining = Some(n);
+        self
+    }
+
+    /// Returns whether the current source appears to be binary, based on whether any of the
+    /// bytes read from it so far were NUL.
+    ///
+    /// Returns `None` if there is no current source.
+    pub fn is_binary(&self) -> Option<bool> {
+        self.state.as_ref().map(|s| s.binary)
+    }
+
+    /// Retries reading standard input up to `attempts` times, sleeping for `delay` before each
+    /// attempt, if it reaches end of file before yielding any data.
+    ///
+    /// This is for environments where standard input may not be connected right away. Standard
+    /// input is treated as empty once the attempts are exhausted.
+    pub fn retry_empty_stdin(mut self, attempts: usize, delay: Duration) -> Self {
+        self.stdin_retry_attempts = attempts;
+        self.stdin_retry_delay = delay;
+        self
+    }
+
+    /// Returns the 1-based line and column (in bytes) of the next byte to be read from the
+    /// current source.
+    ///
+    /// Returns `(1, 1)` if there is no current source.
+    pub fn line_col(&self) -> (usize, usize) {
+        self.state.as_ref().map_or((1, 1), |s| (s.line, s.column))
+    }
+
+    /// Stops reading at the next source boundary once `flag` is set, typically from another
+    /// thread.
+    ///
+    /// By default a cancelled `FileInput` looks like it reached the end of its input. See
+    /// `cancel_as_error` to change that.
+    pub fn with_cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Reports cancellation as an `ErrorKind::Other` error with the message "cancelled",
+    /// instead of end of input.
+    ///
+    /// `Interrupted` isn't used because helpers like `Read::read_to_end` retry on it, and the
+    /// flag stays set, so they would never return.
+    pub fn cancel_as_error(mut self) -> Self {
+        self.cancel_as_error = true;
+        self
+    }
+
+    /// Fails reads made after `deadline` with `ErrorKind::TimedOut`, naming the current source.
+    ///
+    /// The deadline is checked at the start of every `read` and before each source is opened,
+    /// so a single blocking read may still finish after it.
+    pub fn total_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Checks that the next file exists whenever a source is opened, so a missing file is
+    /// reported a little earlier.
+    ///
+    /// If the next file can't be queried, the `read` following the one that opened the current
+    /// source returns the error. Reading can carry on with the current source afterwards.
+    pub fn prevalidate_next(mut self) -> Self {
+        self.prevalidate_next = true;
+        self
+    }
+
+    /// Sets the size of the buffer used when this crate reads the input itself, as in `chunks`,
+    /// `line_length_stats`, and `total_lines`. The default is 8 KiB.
+    ///
+    /// This has no effect on `read`, which always reads into the caller's buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn read_chunk_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "chunk size must be non-zero");
+        self.chunk_size = size;
+        self
+    }
+
+    /// Limits how much data `read_unordered` reads ahead of the consumer.
+    ///
+    /// Worker threads pause before reading another file while at least `bytes` of finished
+    /// reads are waiting to be consumed. Files already being read are read in full, so the limit
+    /// can be exceeded by up to one file per worker.
+    pub fn prefetch_memory_budget(mut self, bytes: usize) -> Self {
+        self.prefetch_memory_budget = Some(bytes);
+        self
+    }
+
+    /// Opens FIFOs without blocking until a writer connects.
+    ///
+    /// FIFO sources are opened with `IoStrategy::open_nonblocking`, and reading from one while
+    /// no data is available fails with `ErrorKind::WouldBlock` rather than blocking, so that
+    /// event-loop based callers can poll. A FIFO with no writer reads as empty.
+    #[cfg(unix)]
+    pub fn nonblocking_fifo(mut self) -> Self {
+        self.nonblocking_fifo = true;
+        self
+    }
+
+    /// Saves the queue of sources that haven't been opened yet.
+    ///
+    /// The current source and the position within it aren't included.
+    pub fn save_state(&self) -> SourceQueueSnapshot {
+        SourceQueueSnapshot { sources: self.queued().into_owned() }
+    }
+
+    /// Replaces the queue of sources with a saved one.
+    ///
+    /// The current source, if any, is closed, so the next `read` starts with the first source
+    /// in the snapshot.
+    pub fn restore_state(&mut self, snapshot: SourceQueueSnapshot) {
+        self.state = None;
+        self.unqueued_paths = None;
+        self.sources = snapshot.sources;
+    }
+
+    /// Returns the current source being read from.
+    ///
+    /// This function will return `None` if no reading has been done yet or all the inputs have
+    /// been drained.
+    pub fn source(&self) -> Option<Source> {
+        self.state.as_ref().map(|s| s.source.clone())
+    }
+
+    /// Returns the source of the data last read, or `Source::Injected` if it was added by
+    /// `FileInput`, along with a count that differs for each source opened.
+    ///
+    /// Together these change whenever the data read starts coming from somewhere else.
+    pub(crate) fn origin(&self) -> (usize, Source) {
+        (self.opened, self.source().unwrap_or(Source::Injected))
+    }
+
+    /// Pauses reading: until `resume` is called, `read` fails with `ErrorKind::WouldBlock`.
+    ///
+    /// Unlike cancellation, nothing is lost; the current source stays open where it was.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes reading after `pause`.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Re-opens the current source so that reading continues from its beginning.
+    ///
+    /// This is useful for retrying a file after a failed or corrupt read. Standard input can't
+    /// be re-opened, so it, or having no current source, is an `ErrorKind::InvalidInput` error.
+    pub fn reopen_current(&mut self) -> io::Result<()> {
+        let source = match self.state {
+            Some(ref state) if !state.source.is_stdin() => state.source.clone(),
+            Some(_) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot reopen stdin"))
+            }
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no current source")),
+        };
+
+        let reader = self.open_source(&source)?;
+        let state = self.state.as_mut().unwrap();
+        state.reader = reader;
+        state.delivered = 0;
+        state.binary = false;
+        state.line = 1;
+        state.column = 1;
+        state.head_remaining = self.head_per_source;
+        Ok(())
+    }
+
+    /// Returns whether the current source supports seeking, as it was when it was opened.
+    ///
+    /// Files opened with `IoStrategy::open_seekable`, including every `Source::FileRange`, can
+    /// seek, as can files the `IoStrategy` reports as seekable when they're opened, such as
+    /// regular files opened by `IoDefault`. Standard input, pipes, missing source placeholders,
+    /// and having no current source can't.
+    pub fn current_is_seekable(&self) -> bool {
+        match self.state {
+            Some(ref state) => state.seekable,
+            None => false,
+        }
+    }
+
+    /// Returns whether the current source is standard input and it's an interactive terminal,
+    /// as reported by `IoStrategy::stdin_is_terminal`.
+    ///
+    /// A command-line tool can use this to tell the user it's waiting for typed input.
+    pub fn current_stdin_is_tty(&self) -> bool {
+        match self.state {
+            Some(ref state) if state.source.is_stdin() => self.io.stdin_is_terminal(),
+            _ => false,
+        }
+    }
+
+    /// Returns the reader for the current source as `Any`, or `None` if no source is open.
+    ///
+    /// Files are read with the reader `IoStrategy::open_any` returns, so with `IoDefault` this
+    /// downcasts to `std::fs::File`. Options that wrap the reader, such as `skip_binary` or a
+    /// strategy's `buffer_capacity_for`, change its type to that of the wrapper.
+    pub fn current_reader(&self) -> Option<&dyn Any> {
+        self.state.as_ref().map(|state| (*state.reader).as_any())
+    }
+
+    /// Returns the `TypeId` of the reader for the current source, or `None` if no source is
+    /// open. See `current_reader`.
+    pub fn current_reader_type_id(&self) -> Option<TypeId> {
+        self.current_reader().map(|reader| reader.type_id())
+    }
+
+    /// Returns the file descriptor of the current source, or `None` if no source is open or its
+    /// reader isn't a `std::fs::File`. See `current_reader`.
+    ///
+    /// The descriptor is only valid until the source is closed.
+    #[cfg(unix)]
+    pub fn current_raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        use std::os::unix::io::AsRawFd;
+
+        self.current_reader()?.downcast_ref::<fs::File>().map(AsRawFd::as_raw_fd)
+    }
+
+    /// Returns the handle of the current source, or `None` if no source is open or its reader
+    /// isn't a `std::fs::File`. See `current_reader`.
+    ///
+    /// The handle is only valid until the source is closed.
+    #[cfg(windows)]
+    pub fn current_raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+        use std::os::windows::io::AsRawHandle;
+
+        self.current_reader()?.downcast_ref::<fs::File>().map(AsRawHandle::as_raw_handle)
+    }
+
+    /// Returns the offset in the combined input at which each source opened so far began, in
+    /// order.
+    ///
+    /// Empty sources are included, so several sources may begin at the same offset.
+    pub fn boundaries(&self) -> &[(usize, Source)] {
+        &self.boundaries
+    }
+
+    /// Returns the contents of the header source set up by `header_source`.
+    ///
+    /// This is empty until the header has been read, which happens on the first read.
+    pub fn header_bytes(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// Returns metadata about the file at `path`.
+    ///
+    /// Metadata is queried through the `IoStrategy` at most once per path and cached for the
+    /// lifetime of this `FileInput`. Errors are not cached.
+    pub fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        if let Some(metadata) = self.metadata_cache.borrow().get(path) {
+            return Ok(metadata.clone());
+        }
+
+        let metadata = self.io.metadata(path)?;
+        self.metadata_cache.borrow_mut().insert(path.to_string(), metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Forgets all cached metadata, so files are queried afresh.
+    pub fn clear_metadata_cache(&mut self) {
+        self.metadata_cache.borrow_mut().clear();
+    }
+
+    /// Returns whether the underlying readers are already buffered, as reported by the
+    /// `IoStrategy`.
+    pub fn is_buffered(&self) -> bool {
+        self.io.is_buffered()
+    }
+
+    /// Reports whether each of the current and remaining sources is missing, empty, or not,
+    /// without consuming any input.
+    ///
+    /// Metadata is queried afresh through the `IoStrategy`. Errors other than
+    /// `ErrorKind::NotFound` are returned.
+    pub fn classify_sources(&self) -> io::Result<Vec<(Source, SourceStatus)>> {
+        let current = self.state.as_ref().map(|s| &s.source);
+        let queued = self.queued();
+        let remaining = queued.iter().take_while(|s| **s != Source::Stop);
+        let mut statuses = Vec::new();
+
+        for source in current.into_iter().chain(remaining) {
+            let status = match source.path() {
+                Some(path) => {
+                    match self.io.metadata(path).map(|metadata| source.len_within(metadata.len())) {
+                        Ok(0) => SourceStatus::Empty,
+                        Ok(len) => SourceStatus::NonEmpty(len),
+                        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                            SourceStatus::Missing
+                        }
+                        Err(e) => return Err(source_error(source, e)),
+                    }
+                }
+                None => SourceStatus::Stdin,
+            };
+            statuses.push((source.clone(), status));
+        }
+
+        Ok(statuses)
+    }
+
+    /// Counts the lines in the current and remaining sources without consuming any input.
+    ///
+    /// Each file is opened and scanned separately, and a final line without a terminator
+    /// counts as a line. Standard input can't be counted in advance, so its presence is an
+    /// `ErrorKind::InvalidInput` error.
+    pub fn total_lines(&self) -> io::Result<usize> {
+        let current = self.state.as_ref().map(|s| &s.source);
+        let queued = self.queued();
+        let remaining = queued.iter().take_while(|s| **s != Source::Stop);
+        let mut total = 0;
+
+        for source in current.into_iter().chain(remaining) {
+            let mut reader: Box<dyn Read + Send> = match *source {
+                Source::File(ref path) => self.io.open(path)?,
+                Source::FileRange { ref path, offset, length } => {
+                    open_range(&self.io, path, offset, length)?
+                }
+                _ => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                              "cannot count lines from stdin in advance"))
+                }
+            };
+            let mut buf = vec![0; self.chunk_size];
+            let mut last = b'\n';
+            loop {
+                let bytes_read = reader.read(&mut buf)?;
+                if bytes_read == 0 {
+                    break;
+                }
+                total += buf[..bytes_read].iter().filter(|&&b| b == b'\n').count();
+                last = buf[bytes_read - 1];
+            }
+            if last != b'\n' {
+                total += 1;
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Wraps this `FileInput` in a `BufReader` and returns it as a boxed `BufRead`.
+    pub fn into_buf_read(self) -> Box<dyn BufRead>
+        where Io: 'static
+    {
+        Box::new(BufReader::new(self))
+    }
+
+    /// Like `into_buf_read`, but with a `BufReader` of the specified capacity.
+    pub fn into_buf_read_with_capacity(self, capacity: usize) -> Box<dyn BufRead>
+        where Io: 'static
+    {
+        Box::new(BufReader::with_capacity(capacity, self))
+    }
+
+    /// Returns an iterator over the lines of the combined input as raw bytes, paired with the
+    /// source each came from.
+    ///
+    /// Lines are split and stripped like `BufRead::lines` and never span sources, but aren't
+    /// required to be UTF-8. A final line without a terminator is yielded unless
+    /// `include_incomplete_final_line` turns that off.
+    pub fn byte_lines(self) -> ByteLines<Io> {
+        ByteLines::new(self)
+    }
+
+    /// Returns an iterator over the lines of every source that carries on to the next source
+    /// when one fails to open or read.
+    ///
+    /// Lines are split and stripped like `BufRead::lines`, but never span two sources. A
+    /// character split between two sources is carried over to the first line of the next.
+    pub fn robust_lines(self) -> RobustLines<Io> {
+        RobustLines::new(self)
+    }
+
+    /// Returns an iterator over the lines of the next two sources side by side, like the two
+    /// halves of a diff.
+    ///
+    /// Once one source runs out of lines, its side of each pair is `None`. A source that fails
+    /// to open is reported as an error first, and then treated as empty. Any sources after the
+    /// first two are ignored.
+    pub fn pair_lines(self) -> PairLines {
+        PairLines::new(self)
+    }
+
+    /// Returns an iterator over the chunks of data returned by each `read`, paired with the
+    /// source each came from.
+    pub fn chunks(self) -> Chunks<Io> {
+        Chunks::new(self)
+    }
+
+    /// Returns an iterator over the CSV records of every source.
+    ///
+    /// Quoted fields may contain commas and newlines, so a record can span several lines, but
+    /// never several sources. See `CsvRecords` for details.
+    pub fn csv_records(self) -> CsvRecords<Io> {
+        CsvRecords::new(self)
+    }
+
+    /// Returns an iterator over the full contents of each source, treating every source as
+    /// exactly one document.
+    ///
+    /// Unlike reading the combined input, document boundaries always line up with source
+    /// boundaries, and empty sources produce empty documents. A source that can't be read
+    /// produces an error, and iteration carries on with the next source.
+    pub fn document_per_file(self) -> Documents<Io> {
+        Documents::new(self)
+    }
+
+    /// Returns an iterator pairing each remaining source with the result of opening it.
+    ///
+    /// A source that fails to open is yielded with its error, and iteration carries on with the
+    /// next source. Sources are opened as they are for the combined input, so options such as
+    /// `recursive` and `skip_binary` apply, but options that act on the combined input, such as
+    /// `max_lines`, don't apply to the readers.
+    pub fn into_source_readers(self) -> SourceReaders<Io> {
+        SourceReaders::new(self)
+    }
+
+    /// Returns an iterator over `size`-byte frames of the combined input.
+    ///
+    /// Frames span source boundaries unless `Frames::within_sources` is used. The final frame
+    /// is shorter than `size` if the input doesn't divide evenly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn frames(self, size: usize) -> Frames<Io> {
+        Frames::new(self, size)
+    }
+
+    /// Returns an iterator over the records of the combined input separated by `delim`, such
+    /// as `b"\r\n\r\n"` between HTTP-header-style blocks.
+    ///
+    /// Records and delimiters may span source boundaries. Delimiters are removed; a final
+    /// record that isn't followed by one is still returned, unless it's empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `delim` is empty.
+    pub fn split_on_bytes(self, delim: &[u8]) -> SplitOnBytes<Io> {
+        SplitOnBytes::new(self, delim)
+    }
+
+    /// Returns an iterator over records that are each preceded by a 4-byte length in the
+    /// byte order `endian`.
+    ///
+    /// Records and their lengths may span source boundaries. Input ending partway through a
+    /// record or length is an `ErrorKind::UnexpectedEof` error.
+    pub fn length_prefixed_frames(self, endian: Endian) -> LengthPrefixedFrames<Io> {
+        LengthPrefixedFrames::new(self, endian)
+    }
+
+    /// Reads all remaining input and returns statistics about the lengths of its lines.
+    ///
+    /// Lines are not retained, so this runs in constant memory.
+    pub fn line_length_stats(&mut self) -> io::Result<LineLengthStats> {
+        stats::line_length_stats(self)
+    }
+
+    /// Returns a reader that applies `f` to every byte of the combined input.
+    pub fn map_bytes<F>(self, f: F) -> MapBytes<Io, F>
+        where F: FnMut(u8) -> u8
+    {
+        MapBytes::new(self, f)
+    }
+
+    /// Returns a reader over the combined input that collapses each run of blank lines into a
+    /// single blank line, like `cat -s`.
+    ///
+    /// Runs are collapsed across source boundaries too.
+    pub fn squeeze_blank_lines(self) -> SqueezeBlankLines<Io> {
+        SqueezeBlankLines::new(self)
+    }
+
+    /// Returns a reader over the combined input that prefixes every line with the name of its
+    /// source and a colon, like `grep -H`.
+    ///
+    /// A line continuing from one source into the next is prefixed only once, with the name
+    /// of the source it started in. Lines of a `stream_header` or `stream_footer` aren't
+    /// prefixed.
+    pub fn prefix_source(self) -> PrefixSource<Io> {
+        PrefixSource::new(self)
+    }
+
+    /// Returns a reader over the combined input that passes each line, without its `\n` or
+    /// `\r\n`, to `f`, like a `sed` or `grep` filter.
+    ///
+    /// When `f` returns `Some`, the returned text is emitted in place of the line and followed by
+    /// the original newline, if there was one; when it returns `None`, the line is dropped.
+    /// Lines continuing from one source into the next are passed whole. Invalid UTF-8 is an
+    /// `ErrorKind::InvalidData` error.
+    pub fn filter_lines<F>(self, f: F) -> FilterLines<Io, F>
+        where F: FnMut(&str) -> Option<String>
+    {
+        FilterLines::new(self, f)
+    }
+
+    /// Returns a reader over the combined input that also writes everything it reads to `w`.
+    ///
+    /// Errors writing to `w` are returned from `read`.
+    pub fn tee<W: Write>(self, w: W) -> Tee<Io, W> {
+        Tee::new(self, w)
+    }
+
+    /// Returns a reader over the combined input along with a `Meter` reporting its throughput.
+    pub fn metered(self) -> (Metered<Io>, Meter) {
+        Metered::new(self)
+    }
+
+    /// Reads every file source concurrently, yielding each one's full contents as it completes.
+    ///
+    /// Results are not in argument order. Standard input cannot be read in parallel, so any
+    /// Standard input entries are read on the calling thread and yielded first. Unless limited
+    /// with `prefetch_memory_budget`, every file may be held in memory at once.
+    ///
+    /// `skip_binary`, `missing_source_placeholder` and `buffer_stdin` apply to each source as
+    /// usual. Options that depend on the order of the sources, `header_source`,
+    /// `require_consistent_bom`, `skip_duplicate_content` and `recursive`, can't be honoured,
+    /// so with any of them set the only item is an `ErrorKind::InvalidInput` error.
+    pub fn read_unordered(mut self) -> Unordered<Io>
+        where Io: Send + Sync + 'static
+    {
+        let unsupported = [(self.header_pending, "header_source"),
+                           (self.require_consistent_bom, "require_consistent_bom"),
+                           (self.skip_duplicate_content, "skip_duplicate_content"),
+                           (self.recursive, "recursive")];
+        if let Some(&(_, option)) = unsupported.iter().find(|&&(set, _)| set) {
+            return Unordered::unsupported(self.io, option);
+        }
+
+        self.queue_paths();
+        if let Some(stop) = self.sources.iter().position(|s| *s == Source::Stop) {
+            self.sources.truncate(stop);
+        }
+        let options = unordered::Options {
+            budget: self.prefetch_memory_budget,
+            skip_binary: self.skip_binary,
+            missing_placeholder: self.missing_placeholder,
+            buffer_stdin: self.buffer_stdin,
+        };
+        Unordered::new(self.io, self.sources, options)
+    }
+
+    /// Reads all remaining input into a string, along with a map of the byte ranges of that
+    /// string that came from each source.
+    ///
+    /// The ranges are in order and partition the string. Sources that yield no data don't
+    /// appear in the map. Like `Read::read_to_string`, invalid UTF-8 is an
+    /// `ErrorKind::InvalidData` error.
+    pub fn read_to_string_with_map(&mut self) -> io::Result<(String, SourceMap)> {
+        let mut data = Vec::new();
+        let mut map: SourceMap = Vec::new();
+        let mut buf = vec![0; self.chunk_size];
+        let mut last_opened = None;
+
+        loop {
+            let bytes_read = self.read(&mut buf)?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            let start = data.len();
+            data.extend_from_slice(&buf[..bytes_read]);
+            let (opened, source) = self.origin();
+            if last_opened == Some((opened, source.clone())) {
+                map.last_mut().unwrap().1.end = data.len();
+            } else {
+                last_opened = Some((opened, source.clone()));
+                map.push((source, start..data.len()));
+            }
+        }
+
+        let string = String::from_utf8(data).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+            })?;
+        Ok((string, map))
+    }
+
+    /// Reads all remaining input and returns how many times `needle` occurs in it.
+    ///
+    /// The input is read through a single buffer of `read_chunk_size` bytes and isn't retained.
+    pub fn count_byte(&mut self, needle: u8) -> io::Result<u64> {
+        let mut buf = vec![0; self.chunk_size];
+        let mut count = 0;
+        loop {
+            let bytes_read = self.read(&mut buf)?;
+            if bytes_read == 0 {
+                return Ok(count);
+            }
+            count += buf[..bytes_read].iter().filter(|&&b| b == needle).count() as u64;
+        }
+    }
+
+    /// Reads and returns the next line, or `None` at the end of the input.
+    ///
+    /// Lines are stripped like `BufRead::lines` and never span sources. Data read beyond the
+    /// line is held for the next call, and is returned first by `read` if that's called
+    /// instead.
+    ///
+    /// A character split between the end of one source and the start of the next is carried
+    /// over to the first line of the next, so only complete characters are decoded.
+    pub fn next_line(&mut self) -> Option<io::Result<Line>> {
+        lines::next_line(self)
+    }
+
+    /// Reads all remaining input into a new temporary file and returns its path, for tools
+    /// that can only read from a named file.
+    ///
+    /// The file is deleted when the returned `TempPath` is dropped, unless it's kept with
+    /// `TempPath::keep`.
+    #[cfg(feature = "tempfile")]
+    pub fn materialize(mut self) -> io::Result<TempPath> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        io::copy(&mut self, &mut file)?;
+        file.flush()?;
+        Ok(file.into_temp_path())
+    }
+
+    /// Reads all remaining input and returns its last `n` lines.
+    ///
+    /// Lines are split and stripped like `BufRead::lines`, and may span sources, so a character
+    /// split between two sources is decoded intact. Only the last `n` lines are kept in
+    /// memory, but all of the input is read.
+    pub fn tail(self, n: usize) -> io::Result<Vec<String>> {
+        lines::tail(self, n)
+    }
+
+    /// Returns the number of bytes left to read, if the strategy can report the size of every
+    /// current and remaining source.
+    ///
+    /// Sizes come from the metadata cache. This is only an estimate: options such as
+    /// `max_lines` or `skip_binary` can make less be read.
+    fn remaining_size_hint(&self) -> Option<u64> {
+        let unread = |cursor: &Cursor<Vec<u8>>| cursor.get_ref().len() as u64 - cursor.position();
+        let mut total = self.line_cursor.buffer.len() as u64 + unread(&self.stream_header) +
+                        unread(&self.stream_footer);
+        if let Some(ref state) = self.state {
+            let size = state.source.len_within(self.metadata(state.source.path()?).ok()?.len());
+            total += size.saturating_sub(state.delivered);
+        }
+
+        for source in self.queued().iter().take_while(|s| **s != Source::Stop) {
+            let size = source.len_within(self.metadata(source.path()?).ok()?.len());
+            total = total.saturating_add(size);
+        }
+        Some(total)
+    }
+
+    /// Applies `newline_style` to added content, once it can no longer change.
+    fn style_injected(&mut self) {
+        if self.injected_styled {
+            return;
+        }
+        self.injected_styled = true;
+
+        if self.newline_style == NewlineStyle::CrLf {
+            for cursor in [&mut self.stream_header, &mut self.stream_footer] {
+                *cursor = Cursor::new(to_crlf(cursor.get_ref()));
+            }
+            if let Some(ref mut placeholder) = self.missing_placeholder {
+                *placeholder = to_crlf(placeholder);
+            }
+        }
+    }
+
+    fn read_footer(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let footer_len = self.stream_footer.read(buf)?;
+        self.total_delivered += footer_len;
+        Ok(footer_len)
+    }
+
+    /// Opens `source`, recording whether it can seek in `opened_seekable`.
+    fn open_source(&mut self, source: &Source) -> io::Result<Box<dyn ReadAny + Send>> {
+        self.opened_seekable = false;
+        let reader = match self.on_open_seek {
+            Some(ref seek) if !matches!(*source, Source::FileRange { .. }) => {
+                let seekable = match *source {
+                    Source::File(ref path) => self.io.open_seekable(path)?,
+                    _ => None,
+                };
+                match seekable {
+                    Some(mut reader) => {
+                        seek(source, &mut reader).map_err(|e| source_error(source, e))?;
+                        self.opened_seekable = true;
+                        Box::new(reader)
+                    }
+                    None if self.require_seekable => {
+                        return Err(source_error(source,
+                                                io::Error::new(io::ErrorKind::Unsupported,
+                                                               "source is not seekable")));
+                    }
+                    None => self.open_unbuffered_source(source)?,
+                }
+            }
+            _ => {
+                let reader = self.open_unbuffered_source(source)?;
+                self.opened_seekable = match *source {
+                    Source::File(ref path) => self.io.is_seekable(path),
+                    Source::FileRange { .. } => true,
+                    _ => false,
+                };
+                reader
+            }
+        };
+        match self.io.buffer_capacity_for(source) {
+            Some(capacity) => Ok(Box::new(BufReader::with_capacity(capacity, reader))),
+            None => Ok(reader),
+        }
+    }
+
+    fn open_unbuffered_source(&mut self, source: &Source) -> io::Result<Box<dyn ReadAny + Send>> {
+        match *source {
+            Source::Stdin | Source::NamedStdin(_) if self.buffer_stdin => {
+                if self.stdin_buffer.is_none() {
+                    let mut buffer = Vec::new();
+                    self.io.stdin()?.read_to_end(&mut buffer)?;
+                    self.stdin_buffer = Some(buffer.into());
+                }
+                Ok(Box::new(Cursor::new(self.stdin_buffer.clone().unwrap())))
+            }
+            Source::Stdin | Source::NamedStdin(_) => Ok(Box::new(self.io.stdin()?)),
+            #[cfg(unix)]
+            Source::File(ref path) if self.nonblocking_fifo && self.is_fifo(path) => {
+                Ok(Box::new(self.io.open_nonblocking(path)?))
+            }
+            Source::File(ref path) => self.io.open_any(path),
+            Source::FileRange { ref path, offset, length } => {
+                open_range(&self.io, path, offset, length).map_err(|e| source_error(source, e))
+            }
+            Source::Stop => Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot open Stop")),
+            Source::Injected => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot open Injected"))
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn is_fifo(&self, path: &str) -> bool {
+        self.metadata(path).map(|m| m.kind() == FileKind::Fifo).unwrap_or(false)
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.queue_paths();
+        if self.sources.first() == Some(&Source::Stop) {
+            self.sources.clear();
+        }
+        self.sources.is_empty()
+    }
+
+    /// Opens the next source that isn't skipped, for the iterators that read each source
+    /// separately.
+    ///
+    /// Sources are opened by `open_next`, like they are for reading the combined input.
+    fn open_next_source(&mut self) -> Option<(Source, io::Result<Box<dyn Read + Send>>)> {
+        loop {
+            match self.open_next()? {
+                (source, Ok(Some(reader))) => return Some((source, Ok(reader))),
+                (source, Err(error)) => return Some((source, Err(error))),
+                (_, Ok(None)) => {}
+            }
+        }
+    }
+
+    /// Takes the next source off the queue and opens it, or returns `None` at the end.
+    ///
+    /// This applies every option that decides whether and how a source is read: `recursive`,
+    /// `missing_source_placeholder`, `header_source`, `skip_binary`,
+    /// `require_consistent_bom`, `skip_duplicate_content` and `strip_leading_bom`. A source
+    /// that's opened but shouldn't be read is returned with `Ok(None)`.
+    fn open_next(&mut self) -> Option<(Source, Opened)> {
+        self.queue_paths();
+        self.style_injected();
+        if let Err(error) = self.expand_directories() {
+            let source = self.sources.remove(0);
+            let error = source_error(&source, error);
+            return Some((source, Err(error)));
+        }
+        if self.at_end() {
+            return None;
+        }
+
+        let source = self.sources.remove(0);
+        let opened = self.open_and_check(&source);
+        Some((source, opened))
+    }
+
+    fn open_and_check(&mut self, source: &Source) -> Opened {
+        self.leading_bom_stripped = false;
+        let mut reader = match self.open_source(source) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound &&
+                          self.missing_placeholder.is_some() => {
+                self.opened_seekable = false;
+                Box::new(Cursor::new(self.missing_placeholder.clone().unwrap()))
+            }
+            opened => opened?,
+        };
+
+        if self.header_pending {
+            self.header_pending = false;
+            reader.read_to_end(&mut self.header).map_err(|e| source_error(source, e))?;
+            return Ok(None);
+        }
+
+        if self.skip_binary {
+            let mut prefix = Vec::new();
+            reader.by_ref()
+                .take(BINARY_PEEK_LEN)
+                .read_to_end(&mut prefix)
+                .map_err(|e| source_error(source, e))?;
+            if prefix.contains(&0) {
+                return Ok(None);
+            }
+            reader = Box::new(Cursor::new(prefix).chain(reader));
+        }
+
+        if self.require_consistent_bom {
+            let mut prefix = Vec::new();
+            reader.by_ref()
+                .take(UTF8_BOM.len() as u64)
+                .read_to_end(&mut prefix)
+                .map_err(|e| source_error(source, e))?;
+            if !prefix.is_empty() {
+                let bom = bom_encoding(&prefix);
+                let first = *self.first_bom.get_or_insert(bom);
+                if bom != first {
+                    let message = format!("byte order mark ({}) differs from the first source's \
+                                           ({})",
+                                          bom.unwrap_or("none"),
+                                          first.unwrap_or("none"));
+                    return Err(source_error(source,
+                                            io::Error::new(io::ErrorKind::InvalidData, message)));
+                }
+            }
+            reader = Box::new(Cursor::new(prefix).chain(reader));
+        }
+
+        if self.skip_duplicate_content {
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content).map_err(|e| source_error(source, e))?;
+            if self.last_content.as_ref() == Some(&content) {
+                return Ok(None);
+            }
+            reader = Box::new(Cursor::new(content.clone()));
+            self.last_content = Some(content);
+        }
+
+        if self.leading_bom_pending {
+            let mut prefix = Vec::new();
+            reader.by_ref()
+                .take(UTF8_BOM.len() as u64)
+                .read_to_end(&mut prefix)
+                .map_err(|e| source_error(source, e))?;
+            if !prefix.is_empty() {
+                self.leading_bom_pending = false;
+            }
+            if prefix == UTF8_BOM {
+                self.leading_bom_stripped = true;
+            } else {
+                reader = Box::new(Cursor::new(prefix).chain(reader));
+            }
+        }
+
+        Ok(Some(reader))
+    }
+
+    fn is_linked_dir(&self, path: &str) -> io::Result<bool> {
+        Ok(self.io.symlink_metadata(path)?.kind() == FileKind::Symlink &&
+           self.metadata(path).map(|metadata| metadata.is_dir()).unwrap_or(false))
+    }
+
+    /// Replaces directories at the front of the queue with their contents, if `recursive`.
+    fn expand_directories(&mut self) -> io::Result<()> {
+        while self.recursive {
+            let path = match self.sources.first() {
+                Some(Source::File(path)) => path.clone(),
+                _ => return Ok(()),
+            };
+            match self.metadata(&path) {
+                Ok(ref metadata) if metadata.is_dir() => {}
+                _ => return Ok(()),
+            }
+
+            // The directory stays queued until it's listed, so that errors can name it.
+            let canonical = self.io.canonicalize(&path)?;
+            if self.visited_dirs.contains(&canonical) {
+                self.sources.remove(0);
+                continue;
+            }
+
+            let mut entries = Vec::new();
+            for entry in self.io.read_dir(&path)? {
+                if !self.follow_symlinks && self.is_linked_dir(&entry)? {
+                    continue;
+                }
+                entries.push(entry);
+            }
+            entries.sort();
+            self.sources.remove(0);
+            self.visited_dirs.insert(canonical);
+
+            let rest = self.sources.split_off(0);
+            self.sources.extend(entries.into_iter().map(Source::File));
+            self.sources.extend(rest);
+        }
+        Ok(())
+    }
+
+    fn open_next_file(&mut self) -> io::Result<()> {
+        debug_assert!(self.state.is_none(), "opened a source while another was open");
+        let (next_source, reader) = match self.open_next() {
+            Some((source, Ok(Some(reader)))) => (source, reader),
+            Some((_, Err(error))) => return Err(error),
+            Some((_, Ok(None))) | None => return Ok(()),
+        };
+
+        let stdin_retries = if next_source.is_stdin() {
+            self.stdin_retry_attempts
+        } else {
+            0
+        };
+
+        self.opened += 1;
+        self.boundaries.push((self.total_delivered, next_source.clone()));
+        self.state = Some(State {
+            source: next_source,
+            reader,
+            announced: false,
+            delivered: if self.leading_bom_stripped { UTF8_BOM.len() as u64 } else { 0 },
+            binary: false,
+            stdin_retries,
+            line: 1,
+            column: 1,
+            head_remaining: self.head_per_source,
+            rechecked_at: None,
+            seekable: self.opened_seekable,
+        });
+        debug_assert!(self.opened == self.boundaries.len(),
+                      "every opened source should have a boundary");
+
+        if self.prevalidate_next {
+            if let Some(source) = self.sources.first() {
+                if let Some(path) = source.path() {
+                    if let Err(error) = self.metadata(path) {
+                        self.pending_error = Some(source_error(source, error));
+                    }
+                }
+            }
+        }
+
+        if self.report_empty_files {
+            self.announce_source();
+        }
+
+        Ok(())
+    }
+
+    fn check_deadline(&self) -> io::Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                let error = io::Error::new(io::ErrorKind::TimedOut, "deadline exceeded");
+                let queued = self.queued();
+                let source = self.state.as_ref().map(|s| &s.source).or(queued.first());
+                match source {
+                    Some(source) => Err(source_error(source, error)),
+                    None => Err(error),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|flag| flag.load(Ordering::SeqCst))
+    }
+
+    fn announce_source(&mut self) {
+        if let Some(ref mut state) = self.state {
+            if !state.announced {
+                state.announced = true;
+                if let Some(ref mut on_open) = self.on_open {
+                    on_open(&state.source);
+                }
+            }
+        }
+    }
+}
+
+impl<Io: IoStrategy> Read for FileInput<Io> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.paused {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "paused"));
+        }
+
+        // An empty buffer would make the current reader look like it's at the end.
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if !self.line_cursor.buffer.is_empty() {
+            let count = buf.len().min(self.line_cursor.buffer.len());
+            buf[..count].copy_from_slice(&self.line_cursor.buffer[..count]);
+            self.line_cursor.buffer.drain(..count);
+            return Ok(count);
+        }
+
+        self.check_deadline()?;
+        self.style_injected();
+        let header_len = self.stream_header.read(buf)?;
+        if header_len > 0 {
+            self.total_delivered += header_len;
+            return Ok(header_len);
+        }
+
+        if let Some(error) = self.pending_error.take() {
+            return Err(error);
+        }
+
+        loop {
+            if self.lines_remaining == Some(0) {
+                return self.read_footer(buf);
+            }
+
+            if self.state.is_none() {
+                if self.at_end() {
+                    return self.read_footer(buf);
+                }
+
+                if self.is_cancelled() {
+                    if self.cancel_as_error {
+                        return Err(io::Error::other("cancelled"));
+                    }
+                    return Ok(0);
+                }
+
+                self.check_deadline()?;
+                self.open_next_file()?;
+                continue;
+            }
+
+            let state = self.state.as_mut().unwrap();
+            if state.head_remaining == Some(0) {
+                self.state = None;
+                continue;
+            }
+
+            let mut bytes_read = state.reader
+                .read(buf)
+                .map_err(|e| source_error(&state.source, e))?;
+            debug_assert!(bytes_read <= buf.len(), "reader returned more bytes than it was given");
+
+            if bytes_read == 0 {
+                if state.stdin_retries > 0 && state.delivered == 0 {
+                    state.stdin_retries -= 1;
+                    thread::sleep(self.stdin_retry_delay);
+                    continue;
+                }
+                if self.recheck_size_at_eof && state.rechecked_at != Some(state.delivered) {
+                    if let Source::File(ref path) = state.source {
+                        state.rechecked_at = Some(state.delivered);
+                        let grown = self.io
+                            .metadata(path)
+                            .map(|metadata| metadata.len() > state.delivered)
+                            .unwrap_or(false);
+                        if grown {
+                            continue;
+                        }
+                    }
+                }
+                if self.stop_at_empty_file && state.delivered == 0 {
+                    self.sources.clear();
+                }
+                let state = self.state.take().unwrap();
+                if let Source::File(ref path) = state.source {
+                    match self.expected_sizes.get(path) {
+                        Some(&expected) if expected != state.delivered => {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                      format!("{}: expected {} bytes, read {}",
+                                                              path,
+                                                              expected,
+                                                              state.delivered)));
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+
+            if let Some(ref mut remaining) = state.head_remaining {
+                bytes_read = count_down_lines(&buf[..bytes_read], remaining);
+            }
+
+            if let Some(ref mut remaining) = self.lines_remaining {
+                bytes_read = count_down_lines(&buf[..bytes_read], remaining);
+            }
+
+            match self.ascii_case {
+                Some(Case::Upper) => buf[..bytes_read].make_ascii_uppercase(),
+                Some(Case::Lower) => buf[..bytes_read].make_ascii_lowercase(),
+                None => {}
+            }
+
+            // Returning zero would signal the end of the input to the caller.
+            debug_assert!(bytes_read > 0, "truncated a read to nothing before the end");
+            state.delivered += bytes_read as u64;
+            self.total_delivered += bytes_read;
+            // Both scans are whole-slice searches the compiler vectorizes, and the NUL search
+            // stops for good once one is found.
+            let data = &buf[..bytes_read];
+            state.binary = state.binary || data.contains(&0);
+            match data.iter().rposition(|&b| b == b'\n') {
+                Some(last) => {
+                    state.line += data.iter().filter(|&&b| b == b'\n').count();
+                    state.column = bytes_read - last;
+                }
+                None => state.column += bytes_read,
+            }
+
+            self.announce_source();
+            if let Some(ref mut inspect) = self.inspect {
+                inspect(&self.state.as_ref().unwrap().source, &buf[..bytes_read]);
+            }
+            return Ok(bytes_read);
+        }
+    }
+
+    /// Reads all remaining input, first reserving room for it in `buf` if the sizes of all
+    /// the sources are known.
+    ///
+    /// At most `MAX_RESERVATION` bytes are reserved up front, and failing to reserve them
+    /// isn't an error; `buf` then grows as usual.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let start = buf.len();
+        self.style_injected();
+        if let Some(hint) = self.remaining_size_hint() {
+            let _ = buf.try_reserve_exact(hint.min(MAX_RESERVATION) as usize);
+        }
+
+        // `buf` is zero-filled out to its capacity once, and `filled` tracks how much of it
+        // holds input, so spare capacity isn't zeroed again before each read.
+        let mut filled = buf.len();
+        let result = loop {
+            if filled == buf.len() {
+                if buf.len() == buf.capacity() {
+                    // Probe before growing, so that an exact reservation isn't outgrown.
+                    let mut probe = [0; 32];
+                    match self.read(&mut probe) {
+                        Ok(0) => break Ok(()),
+                        Ok(bytes_read) => buf.extend_from_slice(&probe[..bytes_read]),
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => break Err(e),
+                    }
+                    filled = buf.len();
+                    continue;
+                }
+                buf.resize(buf.capacity(), 0);
+            }
+
+            match self.read(&mut buf[filled..]) {
+                Ok(0) => break Ok(()),
+                Ok(bytes_read) => filled += bytes_read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            }
+        };
+
+        buf.truncate(filled);
+        result.map(|_| filled - start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    mod source_vec {
+        use super::super::{make_source_vec, Source};
+
+        #[test]
+        fn empty_list_makes_stdin() {
+            let names: Vec<String> = vec![];
+            let paths = make_source_vec(&names, true);
+            assert_eq!(paths, [Source::Stdin]);
+        }
+
+        #[test]
+        fn dash_makes_stdin() {
+            let names = vec!["-"];
+            let paths = make_source_vec(&names, true);
+            assert_eq!(paths, [Source::Stdin]);
+        }
+
+        #[test]
+        fn dash_makes_path_unless_stdin() {
+            let names = vec!["-"];
+            let paths = make_source_vec(&names, false);
+            assert_eq!(paths, [Source::File("-".to_string())]);
+        }
+
+        #[test]
+        fn filename_makes_path() {
+            let names = vec!["example-file"];
+            let paths = make_source_vec(&names, true);
+            assert_eq!(paths, [Source::File("example-file".to_string())]);
+        }
+
+        #[test]
+        fn mixed() {
+            let names = vec!["one", "two", "-", "three"];
+            let paths = make_source_vec(&names, true);
+            assert_eq!(paths,
+                       [Source::File("one".to_string()),
+                        Source::File("two".to_string()),
+                        Source::Stdin,
+                        Source::File("three".to_string())]);
+        }
+    }
+
+    mod fileinput {
+        use super::super::*;
+        use std::env;
+        use std::fs::File;
+        use std::io::{Read, ErrorKind, BufRead, BufReader, SeekFrom};
+        use std::rc::Rc;
+        use std::cell::Cell;
+        use std::sync::Mutex;
+        use std::time::Duration;
+        use test_util::{MockIo, TempDir};
+
+        #[test]
+        fn read_files() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn skip_empty_file() {
+            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn get_source() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let fileinput = FileInput::new(&paths);
+            let mut reader = BufReader::new(fileinput);
+            let mut buffer = String::new();
+
+            assert_eq!(reader.get_ref().source(), None);
+            reader.read_line(&mut buffer).unwrap();
+            assert_eq!(reader.get_ref().source(),
+                       Some(Source::File("testdata/1".to_string())));
+            reader.read_line(&mut buffer).unwrap();
+            assert_eq!(reader.get_ref().source(),
+                       Some(Source::File("testdata/2".to_string())));
+            reader.read_line(&mut buffer).unwrap();
+            reader.read_line(&mut buffer).unwrap();
+            assert_eq!(reader.get_ref().source(), None);
+        }
+
+        #[test]
+        fn empty_files_not_announced_by_default() {
+            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+            let opened = Arc::new(Mutex::new(Vec::new()));
+            let log = opened.clone();
+            let mut fileinput = FileInput::new(&paths)
+                .on_open(move |source| log.lock().unwrap().push(source.clone()));
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(*opened.lock().unwrap(),
+                       [Source::File("testdata/1".to_string()),
+                        Source::File("testdata/2".to_string())]);
+        }
+
+        #[test]
+        fn report_empty_files() {
+            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+            let opened = Arc::new(Mutex::new(Vec::new()));
+            let log = opened.clone();
+            let mut fileinput = FileInput::new(&paths)
+                .report_empty_files()
+                .on_open(move |source| log.lock().unwrap().push(source.clone()));
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+            assert_eq!(*opened.lock().unwrap(),
+                       [Source::File("testdata/1".to_string()),
+                        Source::File("testdata/empty".to_string()),
+                        Source::File("testdata/2".to_string())]);
+        }
+
+        #[test]
+        fn read_on_another_thread() {
+            let fileinput = FileInput::new(&["testdata/1", "testdata/2"]);
+            let handle = ::std::thread::spawn(move || {
+                let mut fileinput = fileinput;
+                let mut buffer = String::new();
+                fileinput.read_to_string(&mut buffer).map(|_| buffer)
+            });
+
+            assert_eq!(handle.join().unwrap().unwrap(), "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn into_buf_read() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let reader = FileInput::new(&paths).into_buf_read_with_capacity(2);
+            let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+
+            assert_eq!(lines, ["One.", "Two.", "Two."]);
+        }
+
+        #[test]
+        fn stop_ends_input() {
+            let paths = vec!["testdata/1"];
+            let mut fileinput = FileInput::new(&paths)
+                .push_stop()
+                .push_source(Source::File("testdata/2".to_string()));
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\n");
+            assert_eq!(fileinput.read(&mut [0; 8]).unwrap(), 0);
+        }
+
+        #[test]
+        fn stop_at_empty_file() {
+            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths).stop_at_empty_file();
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\n");
+        }
+
+        struct CountingIo(Rc<Cell<usize>>);
+
+        impl IoStrategy for CountingIo {
+            fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+                IoDefault.open(path)
+            }
+
+            fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+                IoDefault.stdin()
+            }
+
+            fn metadata(&self, path: &str) -> io::Result<Metadata> {
+                self.0.set(self.0.get() + 1);
+                IoDefault.metadata(path)
+            }
+        }
+
+        struct GrowingIo(Cell<bool>);
+
+        impl IoStrategy for GrowingIo {
+            fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+                IoDefault.open(path)
+            }
+
+            fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+                IoDefault.stdin()
+            }
+
+            fn metadata(&self, path: &str) -> io::Result<Metadata> {
+                if !self.0.replace(true) {
+                    fs::OpenOptions::new().append(true).open(path)?.write_all(b"Appended.\n")?;
+                }
+                IoDefault.metadata(path)
+            }
+        }
+
+        #[test]
+        fn on_open_seek() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths).on_open_seek(|_, reader| {
+                reader.seek(SeekFrom::Start(2))
+            });
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "e.\no.\nTwo.\n");
+        }
+
+        #[test]
+        fn on_open_seek_require_seekable() {
+            let io = MockIo::new().file("a", b"A.\n");
+            let seek = |_: &Source, reader: &mut dyn Seek| reader.seek(SeekFrom::Start(1));
+            let mut buffer = String::new();
+
+            FileInput::with_strategy(&["a"], io).on_open_seek(seek).read_to_string(&mut buffer)
+                .unwrap();
+            assert_eq!(buffer, "A.\n");
+
+            let io = MockIo::new().file("a", b"A.\n");
+            let error = FileInput::with_strategy(&["a"], io)
+                .on_open_seek(seek)
+                .require_seekable()
+                .read_to_string(&mut buffer)
+                .unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Unsupported);
+        }
+
+        #[test]
+        fn stream_header_and_footer() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths)
+                .stream_header(b"<<\n".to_vec())
+                .stream_footer(b">>\n".to_vec());
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+            assert_eq!(fileinput.read(&mut [0; 8]).unwrap(), 0);
+
+            assert_eq!(buffer, "<<\nOne.\nTwo.\nTwo.\n>>\n");
+            assert_eq!(fileinput.boundaries()[1], (8, Source::File("testdata/2".to_string())));
+        }
+
+        #[test]
+        fn stream_header_and_footer_are_injected() {
+            let framed = || {
+                FileInput::new(&["testdata/1", "testdata/2"])
+                    .stream_header(b"<<\n".to_vec())
+                    .stream_footer(b">>\n".to_vec())
+            };
+            let one = Source::File("testdata/1".to_string());
+            let two = Source::File("testdata/2".to_string());
+
+            let chunks: Vec<(Source, Vec<u8>)> = framed().chunks().map(|c| c.unwrap()).collect();
+            assert_eq!(chunks.first().unwrap().0, Source::Injected);
+            assert_eq!(chunks.last().unwrap().0, Source::Injected);
+
+            let mut prefixed = String::new();
+            framed().prefix_source().read_to_string(&mut prefixed).unwrap();
+            assert_eq!(prefixed, "<<\ntestdata/1:One.\ntestdata/2:Two.\ntestdata/2:Two.\n>>\n");
+
+            let (_, map) = framed().read_to_string_with_map().unwrap();
+            assert_eq!(map,
+                       [(Source::Injected, 0..3),
+                        (one.clone(), 3..8),
+                        (two.clone(), 8..18),
+                        (Source::Injected, 18..21)]);
+
+            let mut fileinput = framed();
+            let mut lines = Vec::new();
+            while let Some(line) = fileinput.next_line() {
+                let line = line.unwrap();
+                lines.push((line.source, line.text));
+            }
+            assert_eq!(lines,
+                       [(Source::Injected, "<<".to_string()),
+                        (one.clone(), "One.".to_string()),
+                        (two.clone(), "Two.".to_string()),
+                        (two.clone(), "Two.".to_string()),
+                        (Source::Injected, ">>".to_string())]);
+
+            let byte_lines: Vec<(Source, Vec<u8>)> =
+                framed().byte_lines().map(|l| l.unwrap()).collect();
+            assert_eq!(byte_lines.len(), 5);
+            assert_eq!(byte_lines[4], (Source::Injected, b">>".to_vec()));
+        }
+
+        #[test]
+        fn current_is_seekable() {
+            let mut buf = [0; 4];
+
+            let mut fileinput = FileInput::new(&["testdata/1"]);
+            assert!(!fileinput.current_is_seekable());
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            assert!(fileinput.current_is_seekable());
+
+            let io = MockIo::new().stdin(b"Piped.\n");
+            let mut fileinput = FileInput::with_strategy(&["-"], io);
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            assert!(!fileinput.current_is_seekable());
+
+            let manifest = vec![("testdata/3".to_string(), 7, 7)];
+            let mut fileinput = FileInput::from_manifest(&manifest);
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            assert!(fileinput.current_is_seekable());
+
+            let io = MockIo::new().file("a", b"Data.\n").seekable();
+            let range = Source::FileRange { path: "a".to_string(), offset: 0, length: 5 };
+            let mut fileinput = FileInput::from_sources(vec![range], io);
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            assert!(fileinput.current_is_seekable());
+        }
+
+        #[test]
+        fn count_byte() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            assert_eq!(FileInput::new(&paths).count_byte(b'.').unwrap(), 3);
+            assert_eq!(FileInput::new(&paths).count_byte(b'\t').unwrap(), 0);
+        }
+
+        #[test]
+        fn from_manifest() {
+            let manifest = vec![("testdata/2".to_string(), 0, 3),
+                                ("testdata/1".to_string(), 3, 10),
+                                ("testdata/2".to_string(), 5, 4)];
+            let mut fileinput = FileInput::from_manifest(&manifest);
+            let mut buffer = String::new();
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Two.\nTwo.");
+        }
+
+        #[test]
+        fn from_manifest_keeps_ranges_with_sources() {
+            let manifest = vec![("testdata/2".to_string(), 0, 3),
+                                ("testdata/1".to_string(), 3, 10)];
+            let mut buffer = String::new();
+
+            FileInput::from_manifest(&manifest)
+                .source_range(1, 2)
+                .read_to_string(&mut buffer)
+                .unwrap();
+            assert_eq!(buffer, ".\n");
+
+            buffer.clear();
+            let mut fileinput = FileInput::from_manifest(&manifest).order_by_size(true).unwrap();
+            fileinput.read_to_string(&mut buffer).unwrap();
+            assert_eq!(buffer, ".\nTwo");
+            assert_eq!(fileinput.boundaries()[1].1,
+                       Source::FileRange {
+                           path: "testdata/2".to_string(),
+                           offset: 0,
+                           length: 3,
+                       });
+        }
+
+        #[test]
+        fn current_stdin_is_tty() {
+            let mut buf = [0; 1];
+            let paths = vec!["-", "testdata/1"];
+
+            let io = MockIo::new().stdin(b"Typed.\n").terminal(true).file("testdata/1", b"One.\n");
+            let mut fileinput = FileInput::with_strategy(&paths, io);
+            assert!(!fileinput.current_stdin_is_tty());
+            fileinput.read_exact(&mut buf).unwrap();
+            assert!(fileinput.current_stdin_is_tty());
+            fileinput.read_exact(&mut [0; 7]).unwrap();
+            assert!(!fileinput.current_stdin_is_tty());
+
+            let io = MockIo::new().stdin(b"Piped.\n").terminal(false);
+            let mut fileinput = FileInput::with_strategy(&["-"], io);
+            fileinput.read_exact(&mut buf).unwrap();
+            assert!(!fileinput.current_stdin_is_tty());
+        }
+
+        #[test]
+        fn read_into_empty_buffer() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buf = [0; 3];
+
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 3);
+            assert_eq!(fileinput.read(&mut []).unwrap(), 0);
+            let mut buffer = String::new();
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, ".\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn missing_source_placeholder() {
+            let paths = vec!["testdata/1", "testdata/NOPE", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths)
+                .missing_source_placeholder(b"<missing>\n".to_vec());
+            let mut buffer = String::new();
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\n<missing>\nTwo.\nTwo.\n");
+            assert_eq!(fileinput.boundaries()[1],
+                       (5, Source::File("testdata/NOPE".to_string())));
+        }
+
+        #[test]
+        fn skip_duplicate_content() {
+            let mut buffer = String::new();
+            let paths = vec!["testdata/1", "testdata/1_copy", "testdata/2"];
+            FileInput::new(&paths).skip_duplicate_content().read_to_string(&mut buffer).unwrap();
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+
+            buffer.clear();
+            let paths = vec!["testdata/1", "testdata/2", "testdata/1_copy"];
+            FileInput::new(&paths).skip_duplicate_content().read_to_string(&mut buffer).unwrap();
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\nOne.\n");
+        }
+
+        #[test]
+        fn skip_duplicate_content_compares_bytes() {
+            let io = MockIo::new().file("a", b"One.\n").file("b", b"Owe.\n").file("c", b"Owe.\n");
+            let mut buffer = String::new();
+
+            FileInput::with_strategy(&["a", "b", "c"], io)
+                .skip_duplicate_content()
+                .read_to_string(&mut buffer)
+                .unwrap();
+
+            assert_eq!(buffer, "One.\nOwe.\n");
+        }
+
+        #[test]
+        fn header_source() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths).header_source();
+            assert_eq!(fileinput.header_bytes(), b"");
+
+            let mut buffer = String::new();
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Two.\nTwo.\n");
+            assert_eq!(fileinput.header_bytes(), b"One.\n");
+            assert_eq!(fileinput.boundaries(), [(0, Source::File("testdata/2".to_string()))]);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn current_raw_fd() {
+            use std::os::unix::io::BorrowedFd;
+
+            let mut fileinput = FileInput::new(&["testdata/1"]);
+            assert_eq!(fileinput.current_raw_fd(), None);
+            assert_eq!(fileinput.read(&mut [0; 2]).unwrap(), 2);
+
+            let fd = fileinput.current_raw_fd().unwrap();
+            // SAFETY: the file stays open while `fileinput` is borrowed here.
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            let file = File::from(fd.try_clone_to_owned().unwrap());
+            assert_eq!(file.metadata().unwrap().len(), 5);
+
+            let io = MockIo::new().stdin(b"Piped.\n");
+            let mut fileinput = FileInput::with_strategy(&["-"], io);
+            assert_eq!(fileinput.read(&mut [0; 2]).unwrap(), 2);
+            assert_eq!(fileinput.current_raw_fd(), None);
+        }
+
+        #[test]
+        fn current_reader_downcasts() {
+            let mut fileinput = FileInput::new(&["testdata/1"]);
+            assert!(fileinput.current_reader().is_none());
+            assert_eq!(fileinput.current_reader_type_id(), None);
+
+            assert_eq!(fileinput.read(&mut [0; 2]).unwrap(), 2);
+            assert!(fileinput.current_reader().unwrap().downcast_ref::<File>().is_some());
+            assert_eq!(fileinput.current_reader_type_id(), Some(TypeId::of::<File>()));
+        }
+
+        #[test]
+        fn total_deadline() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut buffer = String::new();
+
+            let error = FileInput::new(&paths)
+                .total_deadline(Instant::now())
+                .read_to_string(&mut buffer)
+                .unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::TimedOut);
+            assert_eq!(error.to_string(), "testdata/1: deadline exceeded");
+
+            FileInput::new(&paths)
+                .total_deadline(Instant::now() + Duration::from_secs(60))
+                .read_to_string(&mut buffer)
+                .unwrap();
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn newline_style() {
+            let paths = vec!["testdata/1"];
+            let mut buffer = String::new();
+
+            FileInput::new(&paths)
+                .stream_header(b"Header\nHeader\r\n".to_vec())
+                .newline_style(NewlineStyle::CrLf)
+                .stream_footer(b"Footer\n".to_vec())
+                .read_to_string(&mut buffer)
+                .unwrap();
+
+            assert_eq!(buffer, "Header\r\nHeader\r\nOne.\nFooter\r\n");
+        }
+
+        #[test]
+        fn newline_style_applies_to_placeholder() {
+            let paths = vec!["testdata/NOPE", "testdata/1"];
+            let mut buffer = String::new();
+
+            FileInput::new(&paths)
+                .missing_source_placeholder(b"<missing>\n".to_vec())
+                .newline_style(NewlineStyle::CrLf)
+                .read_to_string(&mut buffer)
+                .unwrap();
+
+            assert_eq!(buffer, "<missing>\r\nOne.\n");
+        }
+
+        #[test]
+        fn read_to_end_reserves_exactly() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut buffer = Vec::new();
+
+            assert_eq!(FileInput::new(&paths).read_to_end(&mut buffer).unwrap(), 15);
+
+            assert_eq!(buffer, b"One.\nTwo.\nTwo.\n");
+            assert_eq!(buffer.capacity(), 15);
+        }
+
+        #[test]
+        fn read_to_end_with_overstated_size_hint() {
+            let io = MockIo::new().file("a", b"A.\nB.\n").reported_len("a", 1 << 40).seekable();
+            let ranges = vec![Source::FileRange { path: "a".to_string(), offset: 0, length: 1 },
+                              Source::FileRange { path: "a".to_string(), offset: 3, length: 2 }];
+            let mut buffer = Vec::new();
+            FileInput::from_sources(ranges, io).read_to_end(&mut buffer).unwrap();
+            assert_eq!(buffer, b"AB.");
+            assert_eq!(buffer.capacity(), 3);
+
+            let io = MockIo::new().file("h", b"Head.\nTail.\n").reported_len("h", 1 << 40);
+            let mut buffer = Vec::new();
+            FileInput::with_strategy(&["h"], io)
+                .head_per_source(1)
+                .read_to_end(&mut buffer)
+                .unwrap();
+            assert_eq!(buffer, b"Head.\n");
+            assert!(buffer.capacity() as u64 <= MAX_RESERVATION);
+        }
+
+        #[test]
+        fn read_to_end_retries_interrupted_reads() {
+            let io = MockIo::new().interrupted("a", b"A.\n").file("b", b"B.\n");
+            let mut buffer = Vec::new();
+
+            FileInput::with_strategy(&["a", "b"], io).read_to_end(&mut buffer).unwrap();
+
+            assert_eq!(buffer, b"A.\nB.\n");
+        }
+
+        #[test]
+        fn read_to_end_without_size_hint() {
+            let io = MockIo::new().file("a", b"A.\n").stdin(b"Piped.\n");
+            let mut buffer = b"Start.\n".to_vec();
+
+            FileInput::with_strategy(&["a", "-"], io).read_to_end(&mut buffer).unwrap();
+
+            assert_eq!(buffer, b"Start.\nA.\nPiped.\n");
+        }
+
+        #[test]
+        #[cfg(feature = "tempfile")]
+        fn materialize() {
+            let paths = vec!["testdata/1", "testdata/2"];
+
+            let path = FileInput::new(&paths).materialize().unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), "One.\nTwo.\nTwo.\n");
+
+            let location = path.to_path_buf();
+            drop(path);
+            assert!(!location.exists());
+        }
+
+        #[test]
+        fn next_line() {
+            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths).read_chunk_size(7);
+            let mut lines = Vec::new();
+
+            while let Some(line) = fileinput.next_line() {
+                let line = line.unwrap();
+                lines.push((line.source.to_string(),
+                            line.global_line_number,
+                            line.line_number,
+                            line.text));
+            }
+
+            assert_eq!(lines,
+                       [("testdata/1".to_string(), 1, 1, "One.".to_string()),
+                        ("testdata/2".to_string(), 2, 1, "Two.".to_string()),
+                        ("testdata/2".to_string(), 3, 2, "Two.".to_string())]);
+        }
+
+        #[test]
+        fn next_line_then_read() {
+            let mut fileinput = FileInput::new(&["testdata/3"]);
+            let mut rest = String::new();
+
+            assert_eq!(fileinput.next_line().unwrap().unwrap().text, "Three.");
+            fileinput.read_to_string(&mut rest).unwrap();
+
+            assert_eq!(rest, "Three.\nThree.\n");
+        }
+
+        #[test]
+        fn expect_source_size() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut sizes = HashMap::new();
+            sizes.insert("testdata/1".to_string(), 5);
+            sizes.insert("testdata/2".to_string(), 10);
+            let mut buffer = String::new();
+
+            FileInput::new(&paths).expect_source_size(sizes.clone()).read_to_string(&mut buffer)
+                .unwrap();
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+
+            sizes.insert("testdata/1".to_string(), 6);
+            let error = FileInput::new(&paths)
+                .expect_source_size(sizes)
+                .read_to_string(&mut buffer)
+                .unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), "testdata/1: expected 6 bytes, read 5");
+        }
+
+        #[test]
+        fn recheck_size_at_eof() {
+            let dir = TempDir::new("recheck");
+            let paths = vec![dir.join("growing"), "testdata/1".to_string()];
+            fs::write(&paths[0], "Start.\n").unwrap();
+            let mut fileinput = FileInput::with_strategy(&paths, GrowingIo(Cell::new(false)))
+                .recheck_size_at_eof();
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Start.\nAppended.\nOne.\n");
+        }
+
+        #[test]
+        fn metadata_is_cached() {
+            let paths = vec!["testdata/2"];
+            let count = Rc::new(Cell::new(0));
+            let mut fileinput = FileInput::with_strategy(&paths, CountingIo(count.clone()));
+
+            assert_eq!(fileinput.metadata("testdata/2").unwrap().len(), 10);
+            assert_eq!(fileinput.metadata("testdata/2").unwrap().len(), 10);
+            assert_eq!(count.get(), 1);
+
+            fileinput.clear_metadata_cache();
+            fileinput.metadata("testdata/2").unwrap();
+            assert_eq!(count.get(), 2);
+        }
+
+        struct BufferedIo;
+
+        impl IoStrategy for BufferedIo {
+            fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+                Ok(Box::new(BufReader::new(File::open(path)?)))
+            }
+
+            fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+                Ok(Box::new(BufReader::new(io::stdin())))
+            }
+
+            fn is_buffered(&self) -> bool {
+                true
+            }
+        }
+
+        #[test]
+        fn is_buffered() {
+            let paths = vec!["testdata/1"];
+
+            assert!(!FileInput::new(&paths).is_buffered());
+            assert!(FileInput::with_strategy(&paths, BufferedIo).is_buffered());
+            assert!(FileInput::from_pairs(Vec::new()).is_buffered());
+        }
+
+        #[test]
+        fn inspect() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let log = seen.clone();
+            let mut fileinput = FileInput::new(&paths)
+                .inspect(move |_, bytes| log.lock().unwrap().extend_from_slice(bytes));
+            let mut buffer = Vec::new();
+
+            fileinput.read_to_end(&mut buffer).unwrap();
+
+            assert_eq!(buffer, b"One.\nTwo.\nTwo.\n");
+            assert_eq!(*seen.lock().unwrap(), buffer);
+        }
+
+        #[test]
+        fn rewrite_paths() {
+            let paths = vec!["alias", "-", "dropped"];
+            let fileinput = FileInput::new(&paths).rewrite_paths(|path| match path {
+                "alias" => Some("testdata/1".to_string()),
+                _ => None,
+            });
+
+            assert_eq!(fileinput.sources,
+                       [Source::File("testdata/1".to_string()), Source::Stdin]);
+        }
+
+        #[test]
+        fn read_rewritten_path() {
+            let paths = vec!["alias"];
+            let mut fileinput = FileInput::new(&paths)
+                .rewrite_paths(|_| Some("testdata/1".to_string()));
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\n");
+        }
+
+        #[test]
+        fn unbuffered_stdin_is_read_once() {
+            let paths = vec!["-", "-"];
+            let io = MockIo::new().stdin(b"Piped.\n");
+            let mut fileinput = FileInput::with_strategy(&paths, io);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Piped.\n");
+        }
+
+        #[test]
+        fn buffer_stdin() {
+            let paths = vec!["-", "-"];
+            let io = MockIo::new().stdin(b"Piped.\n");
+            let mut fileinput = FileInput::with_strategy(&paths, io).buffer_stdin();
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Piped.\nPiped.\n");
+        }
+
+        #[test]
+        fn push_stdin_named() {
+            let io = MockIo::new().file("a", b"A.\n").stdin(b"Piped.\n");
+            let mut fileinput = FileInput::with_strategy(&["a"], io)
+                .push_stdin_named("first")
+                .push_stdin_named("second")
+                .buffer_stdin();
+            let mut buf = [0; 16];
+            let mut seen = Vec::new();
+
+            while fileinput.read(&mut buf).unwrap() > 0 {
+                seen.push(fileinput.source().unwrap().to_string());
+            }
+
+            assert_eq!(seen, ["a", "<stdin: first>", "<stdin: second>"]);
+        }
+
+        #[test]
+        fn dash_is_stdin() {
+            let paths = vec!["-"];
+
+            assert_eq!(*FileInput::new(&paths).queued(), [Source::Stdin]);
+            assert_eq!(*FileInput::new(&paths).dash_is_stdin(false).queued(),
+                       [Source::File("-".to_string())]);
+            assert_eq!(*FileInput::new(&paths).dash_is_stdin(false).dash_is_stdin(true).queued(),
+                       [Source::Stdin]);
+
+            let io = MockIo::new().file("-", b"Dash.\n").stdin(b"Piped.\n");
+            let mut buffer = String::new();
+            FileInput::with_strategy(&paths, io)
+                .dash_is_stdin(false)
+                .read_to_string(&mut buffer)
+                .unwrap();
+            assert_eq!(buffer, "Dash.\n");
+        }
+
+        #[test]
+        fn dash_is_stdin_keeps_implicit_stdin() {
+            let paths: Vec<String> = vec![];
+
+            assert_eq!(*FileInput::new(&paths).dash_is_stdin(false).queued(), [Source::Stdin]);
+        }
+
+        #[test]
+        fn dash_is_stdin_only_maps_paths() {
+            let fileinput = FileInput::new(&["-"]).push_source(Source::Stdin).dash_is_stdin(false);
+            assert_eq!(*fileinput.queued(), [Source::File("-".to_string()), Source::Stdin]);
+
+            let io = MockIo::new().stdin(b"Piped.\n");
+            let fileinput = FileInput::with_strategy(&["-", "a"], io)
+                .dash_is_stdin(false)
+                .prepend_stdin_if_piped();
+            assert_eq!(fileinput.sources,
+                       [Source::Stdin,
+                        Source::File("-".to_string()),
+                        Source::File("a".to_string())]);
+
+            let fileinput = FileInput::from_pairs(vec![("-".to_string(), b"Dash.\n".to_vec())])
+                .dash_is_stdin(true);
+            assert_eq!(fileinput.sources, [Source::File("-".to_string())]);
+        }
+
+        #[test]
+        fn ascii_case() {
+            let mut upper = String::new();
+            FileInput::new(&["testdata/1"]).ascii_case(Case::Upper).read_to_string(&mut upper)
+                .unwrap();
+            assert_eq!(upper, "ONE.\n");
+
+            let mut lower = String::new();
+            FileInput::from_pairs(vec![("a".to_string(), "ÄbC\n".as_bytes().to_vec())])
+                .ascii_case(Case::Lower)
+                .read_to_string(&mut lower)
+                .unwrap();
+            assert_eq!(lower, "Äbc\n");
+        }
+
+        #[test]
+        fn pause_and_resume() {
+            let paths = vec!["testdata/2", "testdata/1"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buf = [0; 4];
+
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            fileinput.pause();
+            assert_eq!(fileinput.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+            fileinput.resume();
+            let mut rest = String::new();
+            fileinput.read_to_string(&mut rest).unwrap();
+
+            assert_eq!(rest, "\nTwo.\nOne.\n");
+        }
+
+        #[test]
+        fn reopen_current() {
+            let paths = vec!["testdata/3", "testdata/1"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buf = [0; 4];
+
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            fileinput.reopen_current().unwrap();
+            let mut rest = String::new();
+            fileinput.read_to_string(&mut rest).unwrap();
+
+            assert_eq!(rest, "Three.\nThree.\nThree.\nOne.\n");
+        }
+
+        #[test]
+        fn reopen_current_rejects_stdin() {
+            let io = MockIo::new().stdin(b"Piped.\n");
+            let mut fileinput = FileInput::with_strategy(&["-"], io);
+            let mut buf = [0; 4];
+
+            assert_eq!(fileinput.reopen_current().unwrap_err().kind(), ErrorKind::InvalidInput);
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            assert_eq!(fileinput.reopen_current().unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+
+        #[test]
+        fn require_consistent_bom() {
+            let paths = vec!["testdata/bom", "testdata/empty", "testdata/bom", "testdata/1"];
+            let mut fileinput = FileInput::new(&paths).require_consistent_bom();
+            let mut buffer = String::new();
+
+            let error = fileinput.read_to_string(&mut buffer).unwrap_err();
+
+            assert_eq!(buffer, "\u{feff}BOM.\n\u{feff}BOM.\n");
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            assert_eq!(error.to_string(),
+                       "testdata/1: byte order mark (none) differs from the first source's (UTF-8)");
+        }
+
+        #[test]
+        fn strip_leading_bom() {
+            let paths = vec!["testdata/empty", "testdata/bom", "testdata/bom"];
+            let mut buffer = String::new();
+
+            FileInput::new(&paths).strip_leading_bom().read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "BOM.\n\u{feff}BOM.\n");
+        }
+
+        #[test]
+        fn strip_leading_bom_counts_toward_source_size() {
+            let mut sizes = HashMap::new();
+            sizes.insert("testdata/bom".to_string(), 8);
+            let mut buffer = String::new();
+
+            FileInput::new(&["testdata/bom"])
+                .strip_leading_bom()
+                .expect_source_size(sizes)
+                .recheck_size_at_eof()
+                .read_to_string(&mut buffer)
+                .unwrap();
+
+            assert_eq!(buffer, "BOM.\n");
+        }
+
+        #[test]
+        fn head_per_source() {
+            let paths = vec!["testdata/2", "testdata/empty", "testdata/3"];
+            let mut buffer = String::new();
+
+            FileInput::new(&paths).head_per_source(1).read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Two.\nThree.\n");
+        }
+
+        #[test]
+        fn boundaries() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(fileinput.boundaries(),
+                       [(0, Source::File("testdata/1".to_string())),
+                        (5, Source::File("testdata/2".to_string()))]);
+        }
+
+        #[test]
+        fn modified_since() {
+            let dir = TempDir::new("modified-since");
+            let threshold = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+            for &(name, offset) in &[("old", 0), ("new", 2_000_000)] {
+                fs::write(dir.join(name), name).unwrap();
+                File::options()
+                    .write(true)
+                    .open(dir.join(name))
+                    .unwrap()
+                    .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(offset))
+                    .unwrap();
+            }
+            let paths = vec![dir.join("old"), "-".to_string(), dir.join("new")];
+
+            let fileinput = FileInput::new(&paths).modified_since(threshold).unwrap();
+
+            assert_eq!(fileinput.sources, [Source::Stdin, Source::File(dir.join("new"))]);
+        }
+
+        #[test]
+        fn order_by_size() {
+            let paths = vec!["testdata/3", "-", "testdata/1", "testdata/4", "testdata/2"];
+            let names = |fileinput: FileInput| -> Vec<String> {
+                fileinput.sources.iter().map(|s| s.to_string()).collect()
+            };
+
+            assert_eq!(names(FileInput::new(&paths).order_by_size(true).unwrap()),
+                       ["testdata/1", "testdata/2", "testdata/3", "testdata/4", "<stdin>"]);
+            assert_eq!(names(FileInput::new(&paths).order_by_size(false).unwrap()),
+                       ["testdata/4", "testdata/3", "testdata/2", "testdata/1", "<stdin>"]);
+        }
+
+        #[test]
+        fn classify_sources() {
+            let fileinput = FileInput::new(&["testdata/1", "testdata/empty", "testdata/NOPE"]);
+
+            assert_eq!(fileinput.classify_sources().unwrap(),
+                       vec![(Source::File("testdata/1".to_string()), SourceStatus::NonEmpty(5)),
+                            (Source::File("testdata/empty".to_string()), SourceStatus::Empty),
+                            (Source::File("testdata/NOPE".to_string()), SourceStatus::Missing)]);
+        }
+
+        #[test]
+        fn total_lines() {
+            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths);
+
+            assert_eq!(fileinput.total_lines().unwrap(), 3);
+
+            let mut buffer = String::new();
+            fileinput.read_to_string(&mut buffer).unwrap();
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn total_lines_counts_unterminated_line() {
+            let paths = vec!["a"];
+            let io = MockIo::new().file("a", b"one\ntwo");
+
+            assert_eq!(FileInput::with_strategy(&paths, io).total_lines().unwrap(), 2);
+        }
+
+        #[test]
+        fn total_lines_rejects_stdin() {
+            let paths = vec!["testdata/1", "-"];
+            let fileinput = FileInput::new(&paths);
+
+            assert_eq!(fileinput.total_lines().unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+
+        #[test]
+        fn is_binary() {
+            let paths = vec!["testdata/1", "testdata/binary"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buf = [0; 16];
+
+            assert_eq!(fileinput.is_binary(), None);
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 5);
+            assert_eq!(fileinput.is_binary(), Some(false));
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 8);
+            assert_eq!(fileinput.is_binary(), Some(true));
+        }
+
+        #[test]
+        fn skip_binary() {
+            let paths = vec!["testdata/1", "testdata/binary", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths).skip_binary();
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn peek_errors_name_the_source() {
+            let io = || MockIo::new().failing("bad", b"", ErrorKind::InvalidData);
+            let mut buffer = Vec::new();
+
+            let error = FileInput::with_strategy(&["bad"], io())
+                .skip_binary()
+                .read_to_end(&mut buffer)
+                .unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), "bad: simulated failure");
+
+            let error = FileInput::with_strategy(&["bad"], io())
+                .require_consistent_bom()
+                .read_to_end(&mut buffer)
+                .unwrap_err();
+            assert_eq!(error.to_string(), "bad: simulated failure");
+        }
+
+        #[test]
+        fn max_lines() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths).max_lines(2);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\n");
+        }
+
+        #[test]
+        fn max_lines_zero() {
+            let paths = vec!["testdata/1"];
+            let mut fileinput = FileInput::new(&paths).max_lines(0);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "");
+        }
+
+        #[test]
+        fn source_range() {
+            let paths = vec!["testdata/1", "testdata/2", "testdata/3", "testdata/4"];
+            let mut fileinput = FileInput::new(&paths).source_range(1, 3);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Two.\nTwo.\nThree.\nThree.\nThree.\n");
+        }
+
+        #[test]
+        fn source_range_clamps() {
+            let paths = vec!["testdata/1", "testdata/2"];
+
+            assert_eq!(FileInput::new(&paths).source_range(1, 10).sources,
+                       [Source::File("testdata/2".to_string())]);
+            assert_eq!(FileInput::new(&paths).source_range(5, 10).sources, []);
+        }
+
+        #[test]
+        fn shard() {
+            let paths = vec!["testdata/1", "testdata/2", "testdata/3", "testdata/4"];
+            let mut fileinput = FileInput::new(&paths).shard(0, 2);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nThree.\nThree.\nThree.\n");
+            assert_eq!(FileInput::new(&paths).shard(1, 2).sources,
+                       [Source::File("testdata/2".to_string()),
+                        Source::File("testdata/4".to_string())]);
+        }
+
+        struct LateStdin {
+            empty_reads: usize,
+            data: &'static [u8],
+        }
+
+        impl Read for LateStdin {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.empty_reads > 0 {
+                    self.empty_reads -= 1;
+                    return Ok(0);
+                }
+                self.data.read(buf)
+            }
+        }
+
+        struct LateStdinIo(usize);
+
+        impl IoStrategy for LateStdinIo {
+            fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+                IoDefault.open(path)
+            }
+
+            fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+                Ok(Box::new(LateStdin {
+                    empty_reads: self.0,
+                    data: b"Late.\n",
+                }))
+            }
+        }
+
+        #[test]
+        fn retry_empty_stdin() {
+            let paths = vec!["-", "testdata/1"];
+            let mut fileinput = FileInput::with_strategy(&paths, LateStdinIo(2))
+                .retry_empty_stdin(2, Duration::from_millis(1));
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Late.\nOne.\n");
+        }
+
+        #[test]
+        fn retry_empty_stdin_gives_up() {
+            let paths = vec!["-", "testdata/1"];
+            let mut fileinput = FileInput::with_strategy(&paths, LateStdinIo(3))
+                .retry_empty_stdin(2, Duration::from_millis(1));
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\n");
+        }
+
+        #[test]
+        fn line_col() {
+            let paths = vec!["testdata/1", "testdata/3"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buf = [0; 9];
+
+            assert_eq!(fileinput.line_col(), (1, 1));
+            fileinput.read_exact(&mut buf[..5]).unwrap();
+            assert_eq!(fileinput.line_col(), (2, 1));
+            fileinput.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"Three.\nTh");
+            assert_eq!(fileinput.line_col(), (2, 3));
+            fileinput.read_exact(&mut buf[..3]).unwrap();
+            assert_eq!(fileinput.line_col(), (2, 6));
+            fileinput.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b".\nThree.\n");
+            assert_eq!(fileinput.line_col(), (4, 1));
+        }
+
+        #[test]
+        fn from_pairs() {
+            let fileinput = FileInput::from_pairs(vec![("first".to_string(), b"1\n".to_vec()),
+                                                       ("second".to_string(), b"2\n".to_vec())]);
+            let mut reader = BufReader::new(fileinput);
+            let mut buffer = String::new();
+
+            reader.read_line(&mut buffer).unwrap();
+            assert_eq!(reader.get_ref().source(), Some(Source::File("first".to_string())));
+            reader.read_line(&mut buffer).unwrap();
+            assert_eq!(reader.get_ref().source(), Some(Source::File("second".to_string())));
+            assert_eq!(buffer, "1\n2\n");
+        }
+
+        #[test]
+        fn from_no_pairs() {
+            let mut fileinput = FileInput::from_pairs(vec![]);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(fileinput.sources, []);
+        }
+
+        #[test]
+        fn with_cancel() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let flag = Arc::new(AtomicBool::new(false));
+            let cancel = flag.clone();
+            let mut fileinput = FileInput::new(&paths)
+                .with_cancel(flag)
+                .inspect(move |_, _| cancel.store(true, Ordering::SeqCst));
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\n");
+        }
+
+        #[test]
+        fn cancel_as_error() {
+            let paths = vec!["testdata/1"];
+            let flag = Arc::new(AtomicBool::new(true));
+            let mut fileinput = FileInput::new(&paths).with_cancel(flag).cancel_as_error();
+
+            let result = fileinput.read(&mut [0; 8]);
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Other);
+            assert_eq!(error.to_string(), "cancelled");
+        }
+
+        #[test]
+        fn cancel_as_error_ends_read_to_string() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let flag = Arc::new(AtomicBool::new(false));
+            let cancel = flag.clone();
+            let mut fileinput = FileInput::new(&paths)
+                .with_cancel(flag)
+                .cancel_as_error()
+                .inspect(move |_, _| cancel.store(true, Ordering::SeqCst));
+            let mut buffer = String::new();
+
+            let error = fileinput.read_to_string(&mut buffer).unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::Other);
+            assert_eq!(buffer, "One.\n");
+        }
+
+        #[test]
+        fn prevalidate_next() {
+            let paths = vec!["testdata/1", "testdata/NOPE"];
+            let mut fileinput = FileInput::new(&paths).prevalidate_next();
+            let mut buf = [0; 4];
+
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 4);
+            assert_eq!(&buf, b"One.");
+            let error = fileinput.read(&mut buf).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::NotFound);
+            assert!(error.to_string().contains("testdata/NOPE"));
+            assert_eq!(fileinput.read(&mut buf).unwrap(), 1);
+            assert_eq!(fileinput.read(&mut buf).unwrap_err().kind(), ErrorKind::NotFound);
+        }
+
+        #[test]
+        fn prepend_stdin_if_piped() {
+            let paths = vec!["a"];
+            let io = MockIo::new().file("a", b"File.\n").stdin(b"Piped.\n").terminal(false);
+            let mut fileinput = FileInput::with_strategy(&paths, io).prepend_stdin_if_piped();
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "Piped.\nFile.\n");
+        }
+
+        #[test]
+        fn prepend_stdin_if_piped_skips_terminal() {
+            let paths = vec!["testdata/1"];
+            let io = MockIo::new().terminal(true);
+            let fileinput = FileInput::with_strategy(&paths, io).prepend_stdin_if_piped();
+
+            assert_eq!(fileinput.sources, [Source::File("testdata/1".to_string())]);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn nonblocking_fifo() {
+            use std::env;
+            use std::fs;
+            use std::process::{self, Command};
+            use std::sync::mpsc::channel;
+            use std::thread;
+
+            let path = env::temp_dir().join(format!("fileinput-fifo-{}", process::id()));
+            let path = path.to_str().unwrap().to_string();
+            assert!(Command::new("mkfifo").arg(&path).status().unwrap().success());
+
+            let (tx, rx) = channel();
+            let fifo = path.clone();
+            thread::spawn(move || {
+                let paths = vec![fifo, "testdata/1".to_string()];
+                let mut fileinput = FileInput::new(&paths).nonblocking_fifo();
+                let mut buffer = String::new();
+                tx.send(fileinput.read_to_string(&mut buffer).map(|_| buffer)).unwrap();
+            });
+            let result = rx.recv_timeout(Duration::from_secs(5));
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(result.expect("reading the FIFO blocked").unwrap(), "One.\n");
+        }
+
+        #[test]
+        fn read_error_names_source() {
+            let paths = vec!["good", "bad"];
+            let io = MockIo::new()
+                .file("good", b"fine\n")
+                .failing("bad", b"", ErrorKind::InvalidData);
+            let mut fileinput = FileInput::with_strategy(&paths, io);
+            let mut buffer = String::new();
+
+            let error = fileinput.read_to_string(&mut buffer).unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), "bad: simulated failure");
+        }
+
+        #[test]
+        fn display_source() {
+            assert_eq!(Source::Stdin.to_string(), "<stdin>");
+            assert_eq!(Source::File("testdata/1".to_string()).to_string(), "testdata/1");
+        }
+
+        #[test]
+        fn from_iter() {
+            let paths = (1..3).map(|i| format!("testdata/{}", i));
+            let mut fileinput = FileInput::from_iter(paths);
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn collect_paths() {
+            let fileinput: FileInput = vec!["-", "testdata/1"].into_iter().collect();
+            assert_eq!(*fileinput.queued(),
+                       [Source::Stdin, Source::File("testdata/1".to_string())]);
+
+            let fileinput: FileInput = Vec::<String>::new().into_iter().collect();
+            assert_eq!(*fileinput.queued(), [Source::Stdin]);
+        }
+
+        #[test]
+        fn from_env() {
+            env::set_var("FILEINPUT_TEST_FROM_ENV", "testdata/1\n  testdata/2 ");
+            let mut fileinput = FileInput::from_env("FILEINPUT_TEST_FROM_ENV");
+            let mut buffer = String::new();
+
+            fileinput.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
+        }
+
+        #[test]
+        fn from_unset_env() {
+            let fileinput = FileInput::from_env("FILEINPUT_TEST_UNSET");
+
+            assert_eq!(*fileinput.queued(), [Source::Stdin]);
+        }
+
+        #[test]
+        fn save_and_restore_state() {
+            let paths = vec!["testdata/1", "testdata/2", "testdata/3"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buffer = String::new();
+
+            fileinput.read_exact(&mut [0; 5]).unwrap();
+            let snapshot = fileinput.save_state();
+            assert_eq!(snapshot.sources(),
+                       [Source::File("testdata/2".to_string()),
+                        Source::File("testdata/3".to_string())]);
+
+            fileinput.read_exact(&mut [0; 12]).unwrap();
+            assert_eq!(fileinput.source(), Some(Source::File("testdata/3".to_string())));
+
+            fileinput.restore_state(snapshot.clone());
+            assert_eq!(fileinput.save_state(), snapshot);
+            fileinput.read_to_string(&mut buffer).unwrap();
+            assert_eq!(buffer, "Two.\nTwo.\nThree.\nThree.\nThree.\n");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn recursive_symlink_cycle() {
+            use std::os::unix::fs::symlink;
+
+            let dir = TempDir::new("recursive");
+            fs::create_dir(dir.join("sub")).unwrap();
+            fs::write(dir.join("a"), "A.\n").unwrap();
+            fs::write(dir.join("sub/b"), "B.\n").unwrap();
+            symlink(dir.path(), dir.join("sub/loop")).unwrap();
+
+            for &follow in &[false, true] {
+                let paths = vec![dir.join("")];
+                let mut fileinput = FileInput::new(&paths).recursive().follow_symlinks(follow);
+                let mut buffer = String::new();
+
+                fileinput.read_to_string(&mut buffer).unwrap();
+
+                assert_eq!(buffer, "A.\nB.\n");
+            }
+        }
+
+        #[test]
+        fn directory_without_recursive_is_an_error() {
+            let paths = vec!["testdata"];
+            let mut fileinput = FileInput::new(&paths);
+            let mut buffer = String::new();
+
+            assert!(fileinput.read_to_string(&mut buffer).is_err());
+        }
+
+        #[test]
+        fn read_to_string_with_map() {
+            let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+            let mut fileinput = FileInput::new(&paths).read_chunk_size(3);
+
+            let (string, map) = fileinput.read_to_string_with_map().unwrap();
+
+            assert_eq!(string, "One.\nTwo.\nTwo.\n");
+            assert_eq!(map,
+                       [(Source::File("testdata/1".to_string()), 0..5),
+                        (Source::File("testdata/2".to_string()), 5..15)]);
+        }
+
+        #[test]
+        fn read_to_string_with_map_invalid_utf8() {
+            let paths = vec!["a"];
+            let io = MockIo::new().file("a", b"\xff\n");
+            let mut fileinput = FileInput::with_strategy(&paths, io);
+
+            let error = fileinput.read_to_string_with_map().unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+
+        struct SmallBufferIo;
+
+        impl IoStrategy for SmallBufferIo {
+            fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+                IoDefault.open(path)
+            }
+
+            fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+                IoDefault.stdin()
+            }
+
+            fn buffer_capacity_for(&self, source: &Source) -> Option<usize> {
+                match *source {
+                    Source::File(ref path) if path == "testdata/2" => Some(3),
+                    _ => None,
+                }
+            }
+        }
+
+        #[test]
+        fn buffer_capacity_for() {
+            let paths = vec!["testdata/1", "testdata/2"];
+            let fileinput = FileInput::with_strategy(&paths, SmallBufferIo);
+            let mut reader = BufReader::with_capacity(2, fileinput);
+            let mut buffer = String::new();
+
+            reader.read_to_string(&mut buffer).unwrap();
+
+            assert_eq!(buffer, "One.\nTwo.\nTwo.\n");
         }
 
         #[test]
@@ -0,0 +1,672 @@
+//! Line-oriented iterators over a `FileInput`.
+use std::collections::VecDeque;
+use std::io;
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::mem;
+use std::str;
+
+use {FileInput, Source};
+use strategy::IoStrategy;
+
+
+/// An item produced by `FileInput::robust_lines`.
+#[derive(Debug)]
+pub enum RobustLine {
+    /// A line read successfully, without its line terminator.
+    Line {
+        /// The source the line was read from.
+        source: Source,
+        /// The 1-based line number within `source`.
+        line_number: usize,
+        /// The contents of the line.
+        line: String,
+    },
+    /// An error that ended reading from a source.
+    Error {
+        /// The source that failed.
+        source: Source,
+        /// The error encountered opening or reading the source.
+        error: io::Error,
+    },
+}
+
+/// A line returned by `FileInput::next_line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// The source the line was read from.
+    pub source: Source,
+    /// The 1-based line number within the whole input.
+    pub global_line_number: usize,
+    /// The 1-based line number within `source`.
+    pub line_number: usize,
+    /// The contents of the line, without its line terminator.
+    pub text: String,
+}
+
+/// Data read by `FileInput::next_line` beyond the last line it returned.
+#[derive(Default)]
+pub(crate) struct LineCursor {
+    pub(crate) buffer: Vec<u8>,
+    chunk: Vec<u8>,
+    source: Option<Source>,
+    opened: usize,
+    global_line_number: usize,
+    line_number: usize,
+}
+
+/// A line found by a `LineCursor`, before it's decoded.
+struct RawLine {
+    source: Source,
+    global_line_number: usize,
+    line_number: usize,
+    data: Vec<u8>,
+}
+
+impl LineCursor {
+    fn make_line(&mut self, mut data: Vec<u8>) -> RawLine {
+        if data.ends_with(b"\n") {
+            data.pop();
+            if data.ends_with(b"\r") {
+                data.pop();
+            }
+        }
+
+        self.global_line_number += 1;
+        self.line_number += 1;
+        RawLine {
+            source: self.source.clone().unwrap(),
+            global_line_number: self.global_line_number,
+            line_number: self.line_number,
+            data,
+        }
+    }
+}
+
+/// Returns the length of the incomplete UTF-8 sequence at the end of `data`, which the bytes
+/// that follow it might complete.
+fn incomplete_char_len(data: &[u8]) -> usize {
+    let start = data.len().saturating_sub(3);
+    match data[start..].iter().rposition(|&b| b & 0xc0 != 0x80) {
+        Some(i) => match str::from_utf8(&data[start + i..]) {
+            Err(ref e) if e.error_len().is_none() => data.len() - start - i,
+            _ => 0,
+        },
+        None => 0,
+    }
+}
+
+pub(crate) fn next_line<Io: IoStrategy>(input: &mut FileInput<Io>) -> Option<io::Result<Line>> {
+    let raw = next_raw_line(input, true)?;
+    let RawLine { source, global_line_number, line_number, data } = match raw {
+        Ok(raw) => raw,
+        Err(e) => return Some(Err(e)),
+    };
+    let result = String::from_utf8(data)
+        .map(|text| {
+            Line {
+                source,
+                global_line_number,
+                line_number,
+                text,
+            }
+        })
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "line is not valid UTF-8"));
+    Some(result)
+}
+
+/// Finds the next line, carrying a character split between sources over to the next source if
+/// `carry_chars` is set.
+fn next_raw_line<Io: IoStrategy>(
+    input: &mut FileInput<Io>,
+    carry_chars: bool,
+) -> Option<io::Result<RawLine>> {
+    loop {
+        let cursor = &mut input.line_cursor;
+        if let Some(end) = cursor.buffer.iter().position(|&b| b == b'\n') {
+            let rest = cursor.buffer.split_off(end + 1);
+            let data = mem::replace(&mut cursor.buffer, rest);
+            return Some(Ok(cursor.make_line(data)));
+        }
+
+        let pending = mem::take(&mut cursor.buffer);
+        let mut chunk = mem::take(&mut cursor.chunk);
+        chunk.resize(input.chunk_size, 0);
+        let result = input.read(&mut chunk);
+        let (opened, source) = input.origin();
+        let cursor = &mut input.line_cursor;
+        cursor.chunk = chunk;
+        let bytes_read = match result {
+            Ok(bytes_read) => bytes_read,
+            Err(e) => {
+                cursor.buffer = pending;
+                return Some(Err(e));
+            }
+        };
+        let chunk = &cursor.chunk[..bytes_read];
+
+        if bytes_read == 0 {
+            if pending.is_empty() || !input.include_incomplete_final_line {
+                return None;
+            }
+            return Some(Ok(cursor.make_line(pending)));
+        }
+
+        if cursor.opened == opened && cursor.source.as_ref() == Some(&source) {
+            cursor.buffer = pending;
+            cursor.buffer.extend_from_slice(chunk);
+            continue;
+        }
+
+        // Lines never span sources, so whatever is left of the previous source is its last,
+        // apart from the start of a character that the new source finishes.
+        let mut pending = pending;
+        let mut buffer = if carry_chars {
+            let len = pending.len() - incomplete_char_len(&pending);
+            pending.split_off(len)
+        } else {
+            Vec::new()
+        };
+        buffer.extend_from_slice(chunk);
+        let last = if pending.is_empty() {
+            None
+        } else {
+            Some(Ok(cursor.make_line(pending)))
+        };
+        cursor.buffer = buffer;
+        cursor.source = Some(source);
+        cursor.opened = opened;
+        cursor.line_number = 0;
+        if last.is_some() {
+            return last;
+        }
+    }
+}
+
+/// An iterator over the lines of a `FileInput` as raw bytes, paired with their source.
+///
+/// This struct is created by `FileInput::byte_lines`.
+pub struct ByteLines<Io: IoStrategy> {
+    input: FileInput<Io>,
+}
+
+impl<Io: IoStrategy> ByteLines<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        ByteLines { input }
+    }
+}
+
+impl<Io: IoStrategy> Iterator for ByteLines<Io> {
+    type Item = io::Result<(Source, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = next_raw_line(&mut self.input, false)?;
+        Some(result.map(|raw| (raw.source, raw.data)))
+    }
+}
+
+struct Current {
+    source: Source,
+    reader: BufReader<Box<dyn Read + Send>>,
+    line_number: usize,
+}
+
+/// The last line of a source, held back because it has no terminator until `RobustLines`
+/// knows whether more input follows it, and so whether a character split at its end is
+/// finished by the next source.
+struct Unterminated {
+    source: Source,
+    line_number: usize,
+    data: Vec<u8>,
+}
+
+fn decode_line(source: Source, line_number: usize, data: Vec<u8>) -> RobustLine {
+    match String::from_utf8(data) {
+        Ok(line) => RobustLine::Line { source, line_number, line },
+        Err(_) => {
+            let error = io::Error::new(io::ErrorKind::InvalidData, "line is not valid UTF-8");
+            RobustLine::Error { source, error }
+        }
+    }
+}
+
+/// An iterator over the lines of a `FileInput` that survives per-source errors.
+///
+/// Lines are only decoded once complete, so characters split across reads are intact.
+///
+/// This struct is created by `FileInput::robust_lines`.
+pub struct RobustLines<Io: IoStrategy> {
+    input: FileInput<Io>,
+    current: Option<Current>,
+    unterminated: Option<Unterminated>,
+    queued: VecDeque<RobustLine>,
+}
+
+impl<Io: IoStrategy> RobustLines<Io> {
+    pub(crate) fn new(input: FileInput<Io>) -> Self {
+        RobustLines {
+            input,
+            current: None,
+            unterminated: None,
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Queues the unterminated line held back, now that it's known not to be the final line.
+    ///
+    /// If `carry_char` is set, an incomplete character at the end of the line is returned to
+    /// start the next line instead.
+    fn release_unterminated(&mut self, carry_char: bool) -> Vec<u8> {
+        let Unterminated { source, line_number, mut data } = match self.unterminated.take() {
+            Some(unterminated) => unterminated,
+            None => return Vec::new(),
+        };
+        let carried = if carry_char {
+            let len = data.len() - incomplete_char_len(&data);
+            data.split_off(len)
+        } else {
+            Vec::new()
+        };
+        if !data.is_empty() {
+            self.queued.push_back(decode_line(source, line_number, data));
+        }
+        carried
+    }
+}
+
+impl<Io: IoStrategy> Iterator for RobustLines<Io> {
+    type Item = RobustLine;
+
+    fn next(&mut self) -> Option<RobustLine> {
+        loop {
+            if let Some(item) = self.queued.pop_front() {
+                return Some(item);
+            }
+
+            if self.current.is_none() {
+                match self.input.open_next_source() {
+                    Some((source, Ok(reader))) => {
+                        self.current = Some(Current {
+                            source,
+                            reader: BufReader::new(reader),
+                            line_number: 0,
+                        })
+                    }
+                    Some((source, Err(error))) => {
+                        self.release_unterminated(false);
+                        self.queued.push_back(RobustLine::Error { source, error });
+                    }
+                    None => {
+                        // Nothing follows the line held back, so it's the final line.
+                        let Unterminated { source, line_number, data } =
+                            self.unterminated.take()?;
+                        if !self.input.include_incomplete_final_line {
+                            return None;
+                        }
+                        return Some(decode_line(source, line_number, data));
+                    }
+                }
+                continue;
+            }
+
+            let mut data = Vec::new();
+            let result = self.current.as_mut().unwrap().reader.read_until(b'\n', &mut data);
+            match result {
+                Ok(0) => {
+                    self.current = None;
+                }
+                Ok(_) => {
+                    let mut data = [self.release_unterminated(true), data].concat();
+                    let current = self.current.as_mut().unwrap();
+                    current.line_number += 1;
+                    let (source, line_number) = (current.source.clone(), current.line_number);
+                    if !data.ends_with(b"\n") {
+                        self.unterminated = Some(Unterminated { source, line_number, data });
+                        continue;
+                    }
+
+                    data.pop();
+                    if data.ends_with(b"\r") {
+                        data.pop();
+                    }
+                    let item = decode_line(source, line_number, data);
+                    if let RobustLine::Error { .. } = item {
+                        self.current = None;
+                    }
+                    self.queued.push_back(item);
+                }
+                Err(error) => {
+                    let current = self.current.take().unwrap();
+                    self.release_unterminated(false);
+                    self.queued.push_back(RobustLine::Error {
+                        source: current.source,
+                        error,
+                    });
+                }
+            }
+        }
+    }
+}
+
+type SideLines = Lines<BufReader<Box<dyn Read + Send>>>;
+
+/// An iterator over the lines of two sources side by side.
+///
+/// This struct is created by `FileInput::pair_lines`.
+pub struct PairLines {
+    left: Option<SideLines>,
+    right: Option<SideLines>,
+    error: Option<io::Error>,
+}
+
+impl PairLines {
+    pub(crate) fn new<Io: IoStrategy>(mut input: FileInput<Io>) -> Self {
+        let mut error = None;
+        let mut open_side = || match input.open_next_source() {
+            Some((_, Ok(reader))) => Some(BufReader::new(reader).lines()),
+            Some((_, Err(e))) => {
+                if error.is_none() {
+                    error = Some(e);
+                }
+                None
+            }
+            None => None,
+        };
+        let left = open_side();
+        let right = open_side();
+        PairLines { left, right, error }
+    }
+}
+
+fn next_side_line(side: &mut Option<SideLines>) -> io::Result<Option<String>> {
+    let line = match *side {
+        Some(ref mut lines) => lines.next(),
+        None => return Ok(None),
+    };
+    match line {
+        Some(Ok(line)) => Ok(Some(line)),
+        Some(Err(e)) => {
+            *side = None;
+            Err(e)
+        }
+        None => {
+            *side = None;
+            Ok(None)
+        }
+    }
+}
+
+impl Iterator for PairLines {
+    type Item = io::Result<(Option<String>, Option<String>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        let left = match next_side_line(&mut self.left) {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        let right = match next_side_line(&mut self.right) {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if left.is_none() && right.is_none() {
+            None
+        } else {
+            Some(Ok((left, right)))
+        }
+    }
+}
+
+pub(crate) fn tail<Io: IoStrategy>(input: FileInput<Io>, n: usize) -> io::Result<Vec<String>> {
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+
+    let include_incomplete = input.include_incomplete_final_line;
+    let mut reader = BufReader::new(input);
+    let mut last = VecDeque::with_capacity(n);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        } else if !include_incomplete {
+            break;
+        }
+
+        if last.len() == n {
+            last.pop_front();
+        }
+        last.push_back(line);
+    }
+
+    Ok(last.into_iter().collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::ErrorKind;
+
+    use {FileInput, Source};
+    use super::RobustLine;
+    use test_util::MockIo;
+
+    #[test]
+    fn character_split_between_sources() {
+        let io = || MockIo::new().file("a", b"caf\xc3").file("b", b"\xa9\nOne.\n");
+        let paths = vec!["a", "b"];
+
+        let mut fileinput = FileInput::with_strategy(&paths, io()).read_chunk_size(2);
+        let mut lines = Vec::new();
+        while let Some(line) = fileinput.next_line() {
+            let line = line.unwrap();
+            lines.push((line.source, line.text));
+        }
+        let a = Source::File("a".to_string());
+        let b = Source::File("b".to_string());
+        let expected = vec![(a.clone(), "caf".to_string()),
+                            (b.clone(), "\u{e9}".to_string()),
+                            (b.clone(), "One.".to_string())];
+        assert_eq!(lines, expected);
+
+        let lines: Vec<(Source, String)> = FileInput::with_strategy(&paths, io())
+            .robust_lines()
+            .map(|item| match item {
+                RobustLine::Line { source, line, .. } => (source, line),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(lines, expected);
+
+        let lines: Vec<Vec<u8>> = FileInput::with_strategy(&paths, io())
+            .byte_lines()
+            .map(|line| line.unwrap().1)
+            .collect();
+        assert_eq!(lines, [b"caf\xc3".to_vec(), b"\xa9".to_vec(), b"One.".to_vec()]);
+    }
+
+    #[test]
+    fn unfinished_character_at_end_of_input() {
+        let io = MockIo::new().file("a", b"One.\n").file("b", b"caf\xe2\x82");
+        let items: Vec<RobustLine> =
+            FileInput::with_strategy(&["a", "b"], io).robust_lines().collect();
+
+        assert_eq!(items.len(), 2);
+        match items[1] {
+            RobustLine::Error { ref source, ref error } => {
+                assert_eq!(*source, Source::File("b".to_string()));
+                assert_eq!(error.kind(), ErrorKind::InvalidData);
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn byte_lines_are_not_decoded() {
+        let paths = vec!["testdata/latin1", "testdata/1"];
+        let lines: Vec<(Source, Vec<u8>)> =
+            FileInput::new(&paths).byte_lines().map(|l| l.unwrap()).collect();
+
+        assert_eq!(lines,
+                   [(Source::File("testdata/latin1".to_string()), b"caf\xe9".to_vec()),
+                    (Source::File("testdata/latin1".to_string()), b"na\xefve".to_vec()),
+                    (Source::File("testdata/1".to_string()), b"One.".to_vec())]);
+    }
+
+    #[test]
+    fn robust_lines_continue_after_error() {
+        let io = MockIo::new()
+            .failing("bad", b"partial\n", ErrorKind::InvalidData)
+            .file("good", b"fine\n");
+        let paths = vec!["bad", "good"];
+        let items: Vec<RobustLine> = FileInput::with_strategy(&paths, io).robust_lines().collect();
+
+        assert_eq!(items.len(), 3);
+        match items[0] {
+            RobustLine::Line { ref source, line_number, ref line } => {
+                assert_eq!(*source, Source::File("bad".to_string()));
+                assert_eq!(line_number, 1);
+                assert_eq!(line, "partial");
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+        match items[1] {
+            RobustLine::Error { ref source, ref error } => {
+                assert_eq!(*source, Source::File("bad".to_string()));
+                assert_eq!(error.kind(), ErrorKind::InvalidData);
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+        match items[2] {
+            RobustLine::Line { ref source, line_number, ref line } => {
+                assert_eq!(*source, Source::File("good".to_string()));
+                assert_eq!(line_number, 1);
+                assert_eq!(line, "fine");
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn robust_lines_report_missing_files() {
+        let paths = vec!["testdata/NOPE", "testdata/1"];
+        let items: Vec<RobustLine> = FileInput::new(&paths).robust_lines().collect();
+
+        assert_eq!(items.len(), 2);
+        match items[0] {
+            RobustLine::Error { ref error, .. } => assert_eq!(error.kind(), ErrorKind::NotFound),
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tail() {
+        let paths = vec!["testdata/1", "testdata/2"];
+
+        assert_eq!(FileInput::new(&paths).tail(2).unwrap(), ["Two.", "Two."]);
+        assert_eq!(FileInput::new(&paths).tail(5).unwrap(), ["One.", "Two.", "Two."]);
+        assert!(FileInput::new(&paths).tail(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn incomplete_final_line() {
+        let paths = vec!["testdata/unterminated", "testdata/1", "testdata/unterminated"];
+        for &include in &[true, false] {
+            let lines: Vec<String> = FileInput::new(&paths)
+                .include_incomplete_final_line(include)
+                .robust_lines()
+                .map(|item| match item {
+                    RobustLine::Line { line, .. } => line,
+                    other => panic!("unexpected {:?}", other),
+                })
+                .collect();
+            let mut expected = vec!["No newline.", "At the end.", "One.", "No newline."];
+            if include {
+                expected.push("At the end.");
+            }
+            assert_eq!(lines, expected);
+
+            let tail = FileInput::new(&paths).include_incomplete_final_line(include).tail(1);
+            let expected = if include { "At the end." } else { "No newline." };
+            assert_eq!(tail.unwrap(), [expected]);
+
+            let mut fileinput = FileInput::new(&paths).include_incomplete_final_line(include);
+            let mut count = 0;
+            while let Some(line) = fileinput.next_line() {
+                line.unwrap();
+                count += 1;
+            }
+            assert_eq!(count, if include { 5 } else { 4 });
+        }
+    }
+
+    #[test]
+    fn incomplete_final_line_before_empty_source() {
+        let io = || MockIo::new().file("a", b"One.\nTwo.").file("empty", b"");
+        let paths = vec!["a", "empty"];
+        for &include in &[true, false] {
+            let lines: Vec<String> = FileInput::with_strategy(&paths, io())
+                .include_incomplete_final_line(include)
+                .robust_lines()
+                .map(|item| match item {
+                    RobustLine::Line { line, .. } => line,
+                    other => panic!("unexpected {:?}", other),
+                })
+                .collect();
+            assert_eq!(lines, if include { vec!["One.", "Two."] } else { vec!["One."] });
+        }
+    }
+
+    #[test]
+    fn pair_lines() {
+        let pairs: Vec<_> = FileInput::pair("testdata/2", "testdata/3")
+            .pair_lines()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let line = |s: &str| Some(s.to_string());
+
+        assert_eq!(pairs,
+                   [(line("Two."), line("Three.")),
+                    (line("Two."), line("Three.")),
+                    (None, line("Three."))]);
+    }
+
+    #[test]
+    fn pair_lines_reports_open_errors() {
+        let mut pairs = FileInput::pair("testdata/NOPE", "testdata/1").pair_lines();
+
+        assert_eq!(pairs.next().unwrap().unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(pairs.next().unwrap().unwrap(), (None, Some("One.".to_string())));
+        assert!(pairs.next().is_none());
+    }
+
+    #[test]
+    fn characters_split_across_reads() {
+        let io = MockIo::new().file("euro", &fs::read("testdata/euro").unwrap()).trickle();
+        let lines: Vec<String> = FileInput::with_strategy(&["euro"], io)
+            .robust_lines()
+            .map(|item| match item {
+                RobustLine::Line { line, .. } => line,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(lines, ["Price: 5€", "Cost: 3€"]);
+    }
+
+    #[test]
+    fn characters_split_across_sources() {
+        let io = MockIo::new().file("a", b"Price: 5\xe2\x82").file("b", b"\xac\n").trickle();
+
+        assert_eq!(FileInput::with_strategy(&["a", "b"], io).tail(1).unwrap(), ["Price: 5€"]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Single-pass statistics over the combined input.
+use std::io;
+use std::io::Read;
+
+use FileInput;
+use strategy::IoStrategy;
+
+
+/// Line length statistics produced by `FileInput::line_length_stats`.
+///
+/// Lengths are in bytes and exclude the `\n` terminator. A final line without a terminator is
+/// counted if it's non-empty.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LineLengthStats {
+    /// The number of lines seen.
+    pub lines: u64,
+    /// The length of the shortest line, or 0 if there were no lines.
+    pub min: usize,
+    /// The length of the longest line.
+    pub max: usize,
+    /// The sum of all line lengths.
+    pub total: u64,
+    /// Line counts bucketed by length: bucket 0 counts empty lines, and bucket `k` counts lines
+    /// whose length is in `2^(k-1)..2^k`.
+    pub histogram: Vec<u64>,
+}
+
+impl LineLengthStats {
+    /// Returns the mean line length, or 0 if there were no lines.
+    pub fn mean(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.total as f64 / self.lines as f64
+        }
+    }
+
+    fn record(&mut self, length: usize) {
+        if self.lines == 0 || length < self.min {
+            self.min = length;
+        }
+        if length > self.max {
+            self.max = length;
+        }
+        self.lines += 1;
+        self.total += length as u64;
+
+        let bucket = (usize::BITS - length.leading_zeros()) as usize;
+        if self.histogram.len() <= bucket {
+            self.histogram.resize(bucket + 1, 0);
+        }
+        self.histogram[bucket] += 1;
+    }
+}
+
+pub(crate) fn line_length_stats<Io: IoStrategy>(input: &mut FileInput<Io>)
+                                                -> io::Result<LineLengthStats> {
+    let mut stats = LineLengthStats::default();
+    let mut buf = vec![0; input.chunk_size];
+    let mut length = 0;
+
+    loop {
+        let bytes_read = input.read(&mut buf)?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        for &byte in &buf[..bytes_read] {
+            if byte == b'\n' {
+                stats.record(length);
+                length = 0;
+            } else {
+                length += 1;
+            }
+        }
+    }
+
+    if length > 0 {
+        stats.record(length);
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod test {
+    use FileInput;
+    use test_util::MockIo;
+
+    #[test]
+    fn fixtures() {
+        let paths = vec!["testdata/1", "testdata/2"];
+        let stats = FileInput::new(&paths).line_length_stats().unwrap();
+
+        assert_eq!(stats.lines, 3);
+        assert_eq!(stats.min, 4);
+        assert_eq!(stats.max, 4);
+        assert_eq!(stats.mean(), 4.0);
+        assert_eq!(stats.histogram, [0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn varied_lengths() {
+        let io = MockIo::new().file("a", b"\nabc\n").file("b", b"abcdefgh");
+        let paths = vec!["a", "b"];
+        let stats = FileInput::with_strategy(&paths, io).line_length_stats().unwrap();
+
+        assert_eq!(stats.lines, 3);
+        assert_eq!(stats.min, 0);
+        assert_eq!(stats.max, 8);
+        assert_eq!(stats.mean(), 11.0 / 3.0);
+        assert_eq!(stats.histogram, [1, 0, 1, 0, 1]);
+    }
+}
@@ -0,0 +1,128 @@
+//! Transparent decryption of age-encrypted sources.
+use std::io;
+use std::io::Read;
+use std::iter;
+
+use age::{Decryptor, Identity};
+use age::x25519;
+
+use Source;
+use strategy::{IoDefault, IoStrategy, Metadata};
+
+
+/// Decrypts every source with an age identity.
+///
+/// Files are opened with the wrapped strategy (`IoDefault` unless constructed with
+/// `IoAge::wrap`), and standard input is decrypted with the same identity.
+pub struct IoAge<Io: IoStrategy = IoDefault> {
+    inner: Io,
+    identity: x25519::Identity,
+}
+
+impl IoAge {
+    /// Constructs an `IoAge` that decrypts files from the filesystem with `identity`.
+    pub fn new(identity: x25519::Identity) -> Self {
+        IoAge::wrap(IoDefault, identity)
+    }
+}
+
+impl<Io: IoStrategy> IoAge<Io> {
+    /// Constructs an `IoAge` that decrypts the readers opened by `inner` with `identity`.
+    pub fn wrap(inner: Io, identity: x25519::Identity) -> Self {
+        IoAge { inner, identity }
+    }
+
+    fn decrypt(&self, reader: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        let decryptor = Decryptor::new(reader)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let reader = decryptor.decrypt(iter::once(&self.identity as &dyn Identity))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Box::new(reader))
+    }
+}
+
+impl<Io: IoStrategy> IoStrategy for IoAge<Io> {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let reader = self.inner.open(path)?;
+        self.decrypt(reader)
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        let reader = self.inner.stdin()?;
+        self.decrypt(reader)
+    }
+
+    /// Decrypted readers can't seek, whether or not the wrapped strategy's readers can.
+    fn is_seekable(&self, _path: &str) -> bool {
+        false
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(path)
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        self.inner.canonicalize(path)
+    }
+
+    fn buffer_capacity_for(&self, source: &Source) -> Option<usize> {
+        self.inner.buffer_capacity_for(source)
+    }
+
+    fn stdin_is_terminal(&self) -> bool {
+        self.inner.stdin_is_terminal()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{ErrorKind, Read};
+
+    use FileInput;
+    use strategy::IoStrategy;
+    use super::IoAge;
+    use test_util::MockIo;
+
+    const KEY: &str = "AGE-SECRET-KEY-1FPYFXVXMWGCKQU54G3LS8JXCP2XSTUQZ99VVF4UNNXF0CGJQ355QHLRYLU";
+
+    #[test]
+    fn decrypts_file() {
+        let paths = vec!["testdata/secret.age"];
+        let io = IoAge::new(KEY.parse().unwrap());
+        let mut fileinput = FileInput::with_strategy(&paths, io);
+        let mut buffer = String::new();
+
+        fileinput.read_to_string(&mut buffer).unwrap();
+
+        assert_eq!(buffer, "Secret.\n");
+    }
+
+    #[test]
+    fn plaintext_is_invalid_data() {
+        let paths = vec!["testdata/1"];
+        let io = IoAge::new(KEY.parse().unwrap());
+        let mut fileinput = FileInput::with_strategy(&paths, io);
+        let mut buffer = String::new();
+
+        let result = fileinput.read_to_string(&mut buffer);
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn queries_the_wrapped_strategy() {
+        let inner = MockIo::new().file("secret.age", b"ciphertext").reported_len("secret.age", 99);
+        let io = IoAge::wrap(inner, KEY.parse().unwrap());
+
+        assert_eq!(io.metadata("secret.age").unwrap().len(), 99);
+        assert_eq!(io.metadata("missing.age").unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,177 @@
+//! Decoding sources with caller-supplied codecs.
+use std::io;
+use std::io::{Cursor, Read};
+
+use strategy::{IoDefault, IoStrategy, Metadata};
+
+
+type Decoder = Box<dyn Fn(Box<dyn Read + Send>) -> Box<dyn Read + Send> + Send + Sync>;
+
+/// Maps file extensions and magic bytes to decoders, for use with `IoCodec`.
+///
+/// A decoder is a function wrapping the raw reader of a source in one that decodes it.
+#[derive(Default)]
+pub struct CodecRegistry {
+    extensions: Vec<(String, Decoder)>,
+    magic: Vec<(Vec<u8>, Decoder)>,
+}
+
+impl CodecRegistry {
+    /// Constructs a `CodecRegistry` with no codecs.
+    pub fn new() -> Self {
+        CodecRegistry::default()
+    }
+
+    /// Decodes sources whose paths end in `.extension` with `decoder`.
+    pub fn extension<F>(mut self, extension: &str, decoder: F) -> Self
+        where F: Fn(Box<dyn Read + Send>) -> Box<dyn Read + Send> + Send + Sync + 'static
+    {
+        self.extensions.push((format!(".{}", extension), Box::new(decoder)));
+        self
+    }
+
+    /// Decodes sources that begin with `magic` with `decoder`.
+    ///
+    /// Magic bytes are only checked for sources not matched by extension. The magic bytes are
+    /// passed on to the decoder.
+    pub fn magic<F>(mut self, magic: &[u8], decoder: F) -> Self
+        where F: Fn(Box<dyn Read + Send>) -> Box<dyn Read + Send> + Send + Sync + 'static
+    {
+        self.magic.push((magic.to_vec(), Box::new(decoder)));
+        self
+    }
+
+    fn decode(&self,
+              path: &str,
+              mut reader: Box<dyn Read + Send>)
+              -> io::Result<Box<dyn Read + Send>> {
+        for (extension, decoder) in &self.extensions {
+            if path.ends_with(extension.as_str()) {
+                return Ok(decoder(reader));
+            }
+        }
+
+        let peek_len = self.magic.iter().map(|(magic, _)| magic.len()).max().unwrap_or(0);
+        if peek_len == 0 {
+            return Ok(reader);
+        }
+
+        let mut prefix = Vec::with_capacity(peek_len);
+        reader.by_ref().take(peek_len as u64).read_to_end(&mut prefix)?;
+        let decoder = self.magic.iter().find(|(magic, _)| prefix.starts_with(magic));
+        let reader = Box::new(Cursor::new(prefix).chain(reader));
+        match decoder {
+            Some((_, decoder)) => Ok(decoder(reader)),
+            None => Ok(reader),
+        }
+    }
+}
+
+/// Decodes sources with the codecs in a `CodecRegistry`, opening them with the wrapped strategy
+/// (`IoDefault` unless constructed with `IoCodec::wrap`).
+///
+/// Sources that match no codec and standard input are passed through unchanged.
+pub struct IoCodec<Io: IoStrategy = IoDefault> {
+    inner: Io,
+    registry: CodecRegistry,
+}
+
+impl IoCodec {
+    /// Constructs an `IoCodec` that opens files from the filesystem.
+    pub fn new(registry: CodecRegistry) -> Self {
+        IoCodec::wrap(IoDefault, registry)
+    }
+}
+
+impl<Io: IoStrategy> IoCodec<Io> {
+    /// Constructs an `IoCodec` that opens files with `inner`.
+    pub fn wrap(inner: Io, registry: CodecRegistry) -> Self {
+        IoCodec { inner, registry }
+    }
+}
+
+impl<Io: IoStrategy> IoStrategy for IoCodec<Io> {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let reader = self.inner.open(path)?;
+        self.registry.decode(path, reader)
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        self.inner.stdin()
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(path)
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        self.inner.canonicalize(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::io::Read;
+
+    use FileInput;
+    use super::{CodecRegistry, IoCodec};
+    use test_util::MockIo;
+
+    struct Xor(Box<dyn Read + Send>);
+
+    impl Read for Xor {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let bytes_read = self.0.read(buf)?;
+            for byte in &mut buf[..bytes_read] {
+                *byte ^= 0x20;
+            }
+            Ok(bytes_read)
+        }
+    }
+
+    fn xor(reader: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+        Box::new(Xor(reader))
+    }
+
+    #[test]
+    fn decodes_by_extension() {
+        let io = MockIo::new().file("a.xor", b"oNE\x0e*").file("b", b"Plain.\n");
+        let registry = CodecRegistry::new().extension("xor", xor);
+        let paths = vec!["a.xor", "b"];
+        let mut buffer = String::new();
+
+        FileInput::with_strategy(&paths, IoCodec::wrap(io, registry))
+            .read_to_string(&mut buffer)
+            .unwrap();
+
+        assert_eq!(buffer, "One.\nPlain.\n");
+    }
+
+    #[test]
+    fn decodes_by_magic() {
+        let io = MockIo::new().file("a", b"XOR:\x0e").file("b", b"X");
+        let registry = CodecRegistry::new().magic(b"XOR:", |reader| {
+            let mut reader = reader;
+            let mut magic = [0; 4];
+            reader.read_exact(&mut magic).unwrap();
+            xor(reader)
+        });
+        let paths = vec!["a", "b"];
+        let mut buffer = String::new();
+
+        FileInput::with_strategy(&paths, IoCodec::wrap(io, registry))
+            .read_to_string(&mut buffer)
+            .unwrap();
+
+        assert_eq!(buffer, ".X");
+    }
+}
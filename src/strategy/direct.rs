@@ -0,0 +1,142 @@
+//! Reading files with `O_DIRECT`, bypassing the page cache.
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+
+use libc::{EINVAL, O_DIRECT};
+
+use strategy::IoStrategy;
+
+/// The alignment required of `O_DIRECT` buffers, offsets, and lengths.
+const ALIGNMENT: usize = 4096;
+
+/// The size of the aligned buffer each file is read through.
+const BUFFER_SIZE: usize = 64 * ALIGNMENT;
+
+/// Opens files with `O_DIRECT` so that reads bypass the page cache, for IO benchmarking.
+///
+/// Reads are made through an internal aligned buffer, so callers can use buffers of any size.
+/// Files on filesystems that don't support `O_DIRECT` are read normally. Standard input is
+/// read normally.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IoDirect;
+
+impl IoStrategy for IoDirect {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        match DirectReader::open(path)? {
+            Some(reader) => Ok(Box::new(reader)),
+            None => Ok(Box::new(File::open(path)?)),
+        }
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::stdin()))
+    }
+}
+
+struct DirectReader {
+    file: File,
+    path: String,
+    storage: Vec<u8>,
+    offset: usize,
+    pos: usize,
+    len: usize,
+    started: bool,
+    direct: bool,
+}
+
+impl DirectReader {
+    /// Opens `path` with `O_DIRECT`, or returns `None` if its filesystem rejects `O_DIRECT`,
+    /// which it does with `EINVAL`.
+    fn open(path: &str) -> io::Result<Option<Self>> {
+        match OpenOptions::new().read(true).custom_flags(O_DIRECT).open(path) {
+            Ok(file) => Ok(Some(DirectReader::new(file, path))),
+            Err(ref e) if e.raw_os_error() == Some(EINVAL) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn new(file: File, path: &str) -> Self {
+        let storage = vec![0; BUFFER_SIZE + ALIGNMENT];
+        let misalignment = storage.as_ptr() as usize % ALIGNMENT;
+        let offset = (ALIGNMENT - misalignment) % ALIGNMENT;
+        DirectReader {
+            file,
+            path: path.to_string(),
+            storage,
+            offset,
+            pos: 0,
+            len: 0,
+            started: false,
+            direct: true,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let aligned = &mut self.storage[self.offset..self.offset + BUFFER_SIZE];
+        match self.file.read(aligned) {
+            Ok(len) => {
+                self.pos = 0;
+                self.len = len;
+                self.started = true;
+                Ok(())
+            }
+            Err(ref e) if e.raw_os_error() == Some(EINVAL) && !self.started => {
+                // Some filesystems accept O_DIRECT at open but reject the reads.
+                self.file = File::open(&self.path)?;
+                self.started = true;
+                self.direct = false;
+                self.fill()
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Read for DirectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.len {
+            self.fill()?;
+        }
+
+        let available = &self.storage[self.offset + self.pos..self.offset + self.len];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use FileInput;
+    use super::{DirectReader, IoDirect};
+
+    #[test]
+    fn matches_default() {
+        let paths = vec!["testdata/1", "testdata/empty", "testdata/2", "testdata/3"];
+        let mut direct = Vec::new();
+        let mut default = Vec::new();
+
+        FileInput::with_strategy(&paths, IoDirect).read_to_end(&mut direct).unwrap();
+        FileInput::new(&paths).read_to_end(&mut default).unwrap();
+
+        assert_eq!(direct, default);
+    }
+
+    // tmpfs and overlayfs, common in containers, reject O_DIRECT.
+    #[test]
+    #[ignore = "needs testdata on a filesystem that supports O_DIRECT"]
+    fn reads_with_o_direct() {
+        let mut reader = DirectReader::open("testdata/3").unwrap().expect("O_DIRECT rejected");
+        let mut buffer = String::new();
+
+        reader.read_to_string(&mut buffer).unwrap();
+
+        assert!(reader.direct, "reads with O_DIRECT rejected");
+        assert_eq!(buffer, "Three.\nThree.\nThree.\n");
+    }
+}
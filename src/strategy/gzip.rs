@@ -0,0 +1,112 @@
+//! Transparent decompression of gzip sources.
+use std::io;
+use std::io::Read;
+
+use flate2::read::{GzDecoder, MultiGzDecoder};
+
+use strategy::{IoDefault, IoStrategy, Metadata};
+
+
+/// Decompresses sources whose paths end in `.gz`, opening them with the wrapped strategy
+/// (`IoDefault` unless constructed with `IoGzip::wrap`).
+///
+/// Other paths and standard input are passed through unchanged. A gzip file may contain
+/// several concatenated members, and by default all of them are decoded; see `single_member`.
+pub struct IoGzip<Io: IoStrategy = IoDefault> {
+    inner: Io,
+    multi_member: bool,
+}
+
+impl IoGzip {
+    /// Constructs an `IoGzip` that opens files from the filesystem.
+    pub fn new() -> Self {
+        IoGzip::wrap(IoDefault)
+    }
+}
+
+impl Default for IoGzip {
+    fn default() -> Self {
+        IoGzip::new()
+    }
+}
+
+impl<Io: IoStrategy> IoGzip<Io> {
+    /// Constructs an `IoGzip` that opens files with `inner`.
+    pub fn wrap(inner: Io) -> Self {
+        IoGzip {
+            inner,
+            multi_member: true,
+        }
+    }
+
+    /// Decodes only the first member of each gzip file, ignoring anything after it.
+    pub fn single_member(mut self) -> Self {
+        self.multi_member = false;
+        self
+    }
+}
+
+impl<Io: IoStrategy> IoStrategy for IoGzip<Io> {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let reader = self.inner.open(path)?;
+        if !path.ends_with(".gz") {
+            return Ok(reader);
+        }
+
+        if self.multi_member {
+            Ok(Box::new(MultiGzDecoder::new(reader)))
+        } else {
+            Ok(Box::new(GzDecoder::new(reader)))
+        }
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        self.inner.stdin()
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(path)
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        self.inner.canonicalize(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use FileInput;
+    use super::IoGzip;
+
+    #[test]
+    fn decodes_all_members() {
+        let paths = vec!["testdata/members.gz", "testdata/1"];
+        let mut fileinput = FileInput::with_strategy(&paths, IoGzip::new());
+        let mut buffer = String::new();
+
+        fileinput.read_to_string(&mut buffer).unwrap();
+
+        assert_eq!(buffer, "First member.\nSecond member.\nOne.\n");
+    }
+
+    #[test]
+    fn single_member() {
+        let paths = vec!["testdata/members.gz"];
+        let mut fileinput = FileInput::with_strategy(&paths, IoGzip::new().single_member());
+        let mut buffer = String::new();
+
+        fileinput.read_to_string(&mut buffer).unwrap();
+
+        assert_eq!(buffer, "First member.\n");
+    }
+}
@@ -0,0 +1,132 @@
+//! Fetching `http://` and `https://` sources.
+use std::io;
+use std::io::Read;
+
+use ureq;
+
+use strategy::{IoDefault, IoStrategy, Metadata};
+
+
+/// Fetches sources whose paths are HTTP(S) URLs, opening everything else with the wrapped
+/// strategy (`IoDefault` unless constructed with `IoHttp::wrap`).
+///
+/// A `Source::File("https://example.com/log")` is fetched with a blocking `GET` request and its
+/// response body is read. Responses with an error status are reported as `io::Error`s, with
+/// 404 mapped to `ErrorKind::NotFound`.
+pub struct IoHttp<Io: IoStrategy = IoDefault> {
+    inner: Io,
+}
+
+impl IoHttp {
+    /// Constructs an `IoHttp` that opens non-URL paths from the filesystem.
+    pub fn new() -> Self {
+        IoHttp::wrap(IoDefault)
+    }
+}
+
+impl Default for IoHttp {
+    fn default() -> Self {
+        IoHttp::new()
+    }
+}
+
+impl<Io: IoStrategy> IoHttp<Io> {
+    /// Constructs an `IoHttp` that opens non-URL paths with `inner`.
+    pub fn wrap(inner: Io) -> Self {
+        IoHttp { inner }
+    }
+}
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+impl<Io: IoStrategy> IoStrategy for IoHttp<Io> {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        if !is_url(path) {
+            return self.inner.open(path);
+        }
+
+        match ureq::get(path).call() {
+            Ok(response) => Ok(Box::new(response.into_reader())),
+            Err(ureq::Error::Status(404, _)) => {
+                Err(io::Error::new(io::ErrorKind::NotFound, format!("{}: 404 Not Found", path)))
+            }
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        self.inner.stdin()
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(path)
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        self.inner.canonicalize(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use FileInput;
+    use super::IoHttp;
+
+    fn serve(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(&stream,
+                   "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   status,
+                   body.len(),
+                   body)
+                .unwrap();
+        });
+        format!("http://{}/body", address)
+    }
+
+    #[test]
+    fn mixes_urls_and_files() {
+        let url = serve("200 OK", "Remote.\n");
+        let paths = vec!["testdata/1".to_string(), url];
+        let mut fileinput = FileInput::with_strategy(&paths, IoHttp::new());
+        let mut buffer = String::new();
+
+        fileinput.read_to_string(&mut buffer).unwrap();
+
+        assert_eq!(buffer, "One.\nRemote.\n");
+    }
+
+    #[test]
+    fn not_found() {
+        let url = serve("404 Not Found", "");
+        let paths = vec![url];
+        let mut fileinput = FileInput::with_strategy(&paths, IoHttp::new());
+        let mut buffer = String::new();
+
+        let result = fileinput.read_to_string(&mut buffer);
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
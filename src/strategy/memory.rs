@@ -0,0 +1,50 @@
+//! Sources backed by in-memory buffers.
+use std::collections::HashMap;
+use std::io;
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+
+use strategy::IoStrategy;
+
+
+/// Opens named in-memory buffers instead of files.
+///
+/// Paths that weren't added are `ErrorKind::NotFound`, and standard input is empty. This is
+/// mostly useful for testing code built on `FileInput`; see `FileInput::from_pairs`.
+#[derive(Debug, Default, Clone)]
+pub struct IoMemory {
+    files: HashMap<String, Arc<[u8]>>,
+}
+
+impl IoMemory {
+    /// Constructs an `IoMemory` with no files.
+    pub fn new() -> Self {
+        IoMemory::default()
+    }
+
+    /// Adds a file named `name` with the contents `data`, replacing any previous file of the
+    /// same name.
+    pub fn insert(&mut self, name: String, data: Vec<u8>) {
+        self.files.insert(name, data.into());
+    }
+}
+
+impl IoStrategy for IoMemory {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        match self.files.get(path) {
+            Some(data) => Ok(Box::new(Cursor::new(data.clone()))),
+            None => {
+                Err(io::Error::new(io::ErrorKind::NotFound,
+                                   format!("{}: no such in-memory file", path)))
+            }
+        }
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::empty()))
+    }
+
+    fn is_buffered(&self) -> bool {
+        true
+    }
+}
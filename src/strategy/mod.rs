@@ -0,0 +1,286 @@
+//! Strategies controlling how sources are opened.
+use std::any::Any;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{IsTerminal, Read, Seek, stdin};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::time::SystemTime;
+
+#[cfg(feature = "age")]
+mod age;
+mod codec;
+#[cfg(target_os = "linux")]
+mod direct;
+#[cfg(feature = "gzip")]
+mod gzip;
+#[cfg(feature = "http")]
+mod http;
+mod memory;
+mod vfs;
+#[cfg(feature = "zip")]
+mod zip;
+
+#[cfg(feature = "age")]
+pub use self::age::IoAge;
+pub use self::codec::{CodecRegistry, IoCodec};
+#[cfg(target_os = "linux")]
+pub use self::direct::IoDirect;
+#[cfg(feature = "gzip")]
+pub use self::gzip::IoGzip;
+#[cfg(feature = "http")]
+pub use self::http::IoHttp;
+pub use self::memory::IoMemory;
+pub use self::vfs::{FileSystem, IoVfs};
+#[cfg(feature = "zip")]
+pub use self::zip::IoZip;
+
+use Source;
+#[cfg(unix)]
+use libc;
+
+/// The kind of file a path names, as reported by `Metadata::kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir,
+    /// A symbolic link, only reported by `IoStrategy::symlink_metadata`.
+    Symlink,
+    /// A named pipe.
+    Fifo,
+    /// Anything else, such as a socket or a device.
+    Other,
+}
+
+/// Metadata about a file, as returned by `IoStrategy::metadata`.
+///
+/// Unlike `std::fs::Metadata` this can be constructed, so strategies that don't read from
+/// `std::fs` can report it too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    kind: FileKind,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Metadata {
+    /// Constructs metadata for a file of `kind` that's `len` bytes long, with no known
+    /// modification time.
+    pub fn new(kind: FileKind, len: u64) -> Self {
+        Metadata { kind, len, modified: None }
+    }
+
+    /// Sets the time the file was last modified.
+    pub fn modified_at(mut self, time: SystemTime) -> Self {
+        self.modified = Some(time);
+        self
+    }
+
+    /// Returns the kind of file.
+    pub fn kind(&self) -> FileKind {
+        self.kind
+    }
+
+    /// Returns the size of the file in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether this is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    /// Returns whether this is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    /// Returns the time the file was last modified, failing with `ErrorKind::Unsupported` if
+    /// it isn't known.
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        self.modified.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "modification time is not known")
+        })
+    }
+}
+
+impl From<fs::Metadata> for Metadata {
+    fn from(metadata: fs::Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_file() {
+            FileKind::File
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if is_fifo(&file_type) {
+            FileKind::Fifo
+        } else {
+            FileKind::Other
+        };
+        Metadata { kind, len: metadata.len(), modified: metadata.modified().ok() }
+    }
+}
+
+#[cfg(unix)]
+fn is_fifo(file_type: &fs::FileType) -> bool {
+    file_type.is_fifo()
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_file_type: &fs::FileType) -> bool {
+    false
+}
+
+/// A reader that can also seek, as returned by `IoStrategy::open_seekable`.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// A reader whose concrete type can be recovered, as returned by `IoStrategy::open_any`.
+pub trait ReadAny: Read + Any {
+    /// Returns the reader as `Any`, so it can be downcast to its concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Read + Any> ReadAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Opens the underlying readers for each `Source`.
+///
+/// `FileInput` uses `IoDefault` unless constructed with `FileInput::with_strategy`.
+///
+/// Readers are `Send`, so that a `FileInput` can be moved to another thread whenever its
+/// strategy can.
+pub trait IoStrategy {
+    /// Opens the file at `path` for reading.
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Returns a reader over the process's standard input.
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Opens the file at `path` for reading with a reader whose concrete type can be recovered.
+    ///
+    /// The default boxes the reader `open` returns, so it downcasts only to
+    /// `Box<dyn Read + Send>`. This is used by `FileInput::current_reader`.
+    fn open_any(&self, path: &str) -> io::Result<Box<dyn ReadAny + Send>> {
+        Ok(Box::new(self.open(path)?))
+    }
+
+    /// Opens the file at `path` for reading with a reader that can seek, or returns `None` if
+    /// this strategy's readers can't seek.
+    ///
+    /// This is used by `FileInput::on_open_seek`.
+    fn open_seekable(&self, _path: &str) -> io::Result<Option<Box<dyn ReadSeek + Send>>> {
+        Ok(None)
+    }
+
+    /// Returns whether the file at `path` supports seeking once opened.
+    fn is_seekable(&self, _path: &str) -> bool {
+        false
+    }
+
+    /// Queries metadata about the file at `path`, following symbolic links.
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        fs::metadata(path).map(Metadata::from)
+    }
+
+    /// Queries metadata about the file at `path` without following a symbolic link.
+    ///
+    /// This is used by `FileInput::recursive` to skip linked directories.
+    fn symlink_metadata(&self, path: &str) -> io::Result<Metadata> {
+        fs::symlink_metadata(path).map(Metadata::from)
+    }
+
+    /// Returns the paths of the entries of the directory at `path`, in any order.
+    ///
+    /// This is used by `FileInput::recursive`.
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            match entry?.path().into_os_string().into_string() {
+                Ok(entry) => entries.push(entry),
+                Err(_) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("{}: non-unicode file name", path)))
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Returns the canonical form of `path`, which is the same for every path naming the same
+    /// file.
+    ///
+    /// This is used by `FileInput::recursive` to visit each directory once.
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        fs::canonicalize(path)?.into_os_string().into_string().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData,
+                           format!("{}: non-unicode canonical path", path))
+        })
+    }
+
+    /// Returns whether the readers this strategy opens are already buffered.
+    ///
+    /// Callers can use this to skip wrapping a `FileInput` in a `BufReader`.
+    fn is_buffered(&self) -> bool {
+        false
+    }
+
+    /// Returns the capacity of the `BufReader` that `FileInput` should wrap the reader for
+    /// `source` in, or `None` to use the reader as is.
+    fn buffer_capacity_for(&self, _source: &Source) -> Option<usize> {
+        None
+    }
+
+    /// Returns whether the standard input this strategy reads from is an interactive terminal.
+    fn stdin_is_terminal(&self) -> bool {
+        stdin().is_terminal()
+    }
+
+    /// Opens the FIFO at `path` for reading without waiting for a writer to connect.
+    ///
+    /// Reads from the returned reader fail with `ErrorKind::WouldBlock` while no data is
+    /// available. This is used by `FileInput::nonblocking_fifo`.
+    #[cfg(unix)]
+    fn open_nonblocking(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let file = fs::OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path)?;
+        Ok(Box::new(file))
+    }
+}
+
+/// Reads files from the filesystem and standard input from the process.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IoDefault;
+
+impl IoStrategy for IoDefault {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(stdin()))
+    }
+
+    fn open_any(&self, path: &str) -> io::Result<Box<dyn ReadAny + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn open_seekable(&self, path: &str) -> io::Result<Option<Box<dyn ReadSeek + Send>>> {
+        Ok(Some(Box::new(File::open(path)?)))
+    }
+
+    fn is_seekable(&self, path: &str) -> bool {
+        self.metadata(path).map(|metadata| metadata.is_file()).unwrap_or(false)
+    }
+}
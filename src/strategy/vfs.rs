@@ -0,0 +1,154 @@
+//! Routing file access through a caller-supplied filesystem.
+use std::io;
+use std::io::Read;
+
+use strategy::{IoStrategy, Metadata};
+
+
+/// A minimal filesystem, for reading sources from somewhere other than `std::fs` with `IoVfs`.
+///
+/// Only `open` is required; the other methods fail with `ErrorKind::Unsupported` by default.
+pub trait FileSystem {
+    /// Opens the file at `path` for reading.
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Queries metadata about the file at `path`.
+    fn metadata(&self, _path: &str) -> io::Result<Metadata> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "metadata is not supported"))
+    }
+
+    /// Returns the paths of the entries of the directory at `path`.
+    fn read_dir(&self, _path: &str) -> io::Result<Vec<String>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "listing directories is not supported"))
+    }
+
+    /// Returns the canonical form of `path`.
+    ///
+    /// The default returns `path` unchanged, which suits filesystems without links.
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        Ok(path.to_string())
+    }
+}
+
+/// Opens files through a `FileSystem` instead of `std::fs`.
+///
+/// Standard input is read from the process. `FileInput::recursive` lists directories with
+/// `FileSystem::read_dir`; there are no symbolic links, so every directory is followed.
+pub struct IoVfs<F: FileSystem> {
+    fs: F,
+}
+
+impl<F: FileSystem> IoVfs<F> {
+    /// Constructs an `IoVfs` that opens files from `fs`.
+    pub fn new(fs: F) -> Self {
+        IoVfs { fs }
+    }
+
+    /// Returns the underlying filesystem.
+    pub fn file_system(&self) -> &F {
+        &self.fs
+    }
+}
+
+impl<F: FileSystem> IoStrategy for IoVfs<F> {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        self.fs.open(path)
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::stdin()))
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.fs.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.fs.metadata(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.fs.read_dir(path)
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        self.fs.canonicalize(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::io::{Cursor, ErrorKind, Read};
+
+    use FileInput;
+    use strategy::{FileKind, Metadata};
+    use super::{FileSystem, IoVfs};
+
+    struct Embedded(BTreeMap<&'static str, &'static [u8]>);
+
+    impl FileSystem for Embedded {
+        fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+            match self.0.get(path) {
+                Some(data) => Ok(Box::new(Cursor::new(*data))),
+                None => Err(io::Error::new(ErrorKind::NotFound, "not embedded")),
+            }
+        }
+
+        fn metadata(&self, path: &str) -> io::Result<Metadata> {
+            match self.0.get(path) {
+                Some(data) => Ok(Metadata::new(FileKind::File, data.len() as u64)),
+                None if !self.read_dir(path)?.is_empty() => Ok(Metadata::new(FileKind::Dir, 0)),
+                None => Err(io::Error::new(ErrorKind::NotFound, "not embedded")),
+            }
+        }
+
+        fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+            let prefix = format!("{}/", path);
+            Ok(self.0.keys().filter(|k| k.starts_with(&prefix)).map(|k| k.to_string()).collect())
+        }
+    }
+
+    #[test]
+    fn reads_virtual_files() {
+        let mut files = BTreeMap::new();
+        files.insert("docs/a", &b"A.\n"[..]);
+        files.insert("docs/b", &b"B.\n"[..]);
+        let io = IoVfs::new(Embedded(files));
+        let paths = io.file_system().read_dir("docs").unwrap();
+        let mut buffer = String::new();
+
+        FileInput::with_strategy(&paths, io).read_to_string(&mut buffer).unwrap();
+
+        assert_eq!(buffer, "A.\nB.\n");
+    }
+
+    #[test]
+    fn recursive_lists_virtual_directories() {
+        let mut files = BTreeMap::new();
+        files.insert("docs/b", &b"B.\n"[..]);
+        files.insert("docs/a", &b"A.\n"[..]);
+        files.insert("other", &b"Other.\n"[..]);
+        let mut buffer = String::new();
+
+        FileInput::with_strategy(&["docs"], IoVfs::new(Embedded(files)))
+            .recursive()
+            .read_to_string(&mut buffer)
+            .unwrap();
+
+        assert_eq!(buffer, "A.\nB.\n");
+    }
+
+    #[test]
+    fn reports_constructed_metadata() {
+        let mut files = BTreeMap::new();
+        files.insert("docs/a", &b"A.\n"[..]);
+        let fileinput = FileInput::with_strategy(&["docs/a"], IoVfs::new(Embedded(files)));
+
+        assert_eq!(fileinput.metadata("docs/a").unwrap(), Metadata::new(FileKind::File, 3));
+        assert!(fileinput.metadata("docs").unwrap().is_dir());
+        assert_eq!(fileinput.metadata("docs/a").unwrap().modified().unwrap_err().kind(),
+                   ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,99 @@
+//! Reading entries of a zip archive.
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read};
+use std::sync::Mutex;
+
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use strategy::{IoStrategy, Metadata};
+
+
+/// Opens the entries of a zip archive by name instead of files.
+///
+/// Combined with `FileInput::with_strategy`, the paths given are entry names, so the selected
+/// entries are streamed in the order given. Each entry is decompressed into memory when it's
+/// opened. Names that aren't in the archive are `ErrorKind::NotFound`, and standard input is
+/// read normally.
+pub struct IoZip {
+    archive: Mutex<ZipArchive<File>>,
+}
+
+impl IoZip {
+    /// Opens the zip archive at `path`.
+    pub fn open(path: &str) -> io::Result<Self> {
+        let archive = ZipArchive::new(File::open(path)?).map_err(zip_error)?;
+        Ok(IoZip { archive: Mutex::new(archive) })
+    }
+}
+
+fn zip_error(error: ZipError) -> io::Error {
+    match error {
+        ZipError::Io(error) => error,
+        ZipError::FileNotFound => {
+            io::Error::new(io::ErrorKind::NotFound, "no such entry in zip archive")
+        }
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+    }
+}
+
+impl IoStrategy for IoZip {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let mut archive = self.archive.lock().unwrap();
+        let mut entry = archive.by_name(path).map_err(zip_error)?;
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::stdin()))
+    }
+
+    fn metadata(&self, _path: &str) -> io::Result<Metadata> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "zip entries have no filesystem metadata"))
+    }
+
+    fn is_buffered(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{ErrorKind, Read};
+
+    use FileInput;
+    use super::IoZip;
+
+    #[test]
+    fn reads_entries_in_order() {
+        let paths = vec!["second.txt", "first.txt"];
+        let io = IoZip::open("testdata/entries.zip").unwrap();
+        let mut buffer = String::new();
+
+        FileInput::with_strategy(&paths, io).read_to_string(&mut buffer).unwrap();
+
+        assert_eq!(buffer, "Second entry.\nFirst entry.\n");
+    }
+
+    #[test]
+    fn missing_entry() {
+        let paths = vec!["nope.txt"];
+        let io = IoZip::open("testdata/entries.zip").unwrap();
+        let mut buffer = String::new();
+
+        let error = FileInput::with_strategy(&paths, io).read_to_string(&mut buffer).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn is_buffered() {
+        let paths = vec!["first.txt"];
+        let io = IoZip::open("testdata/entries.zip").unwrap();
+
+        assert!(FileInput::with_strategy(&paths, io).is_buffered());
+    }
+}
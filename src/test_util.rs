@@ -0,0 +1,216 @@
+//! Helpers shared by the unit tests.
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::io;
+use std::io::{Cursor, ErrorKind, Read};
+
+use strategy::{FileKind, IoStrategy, Metadata, ReadSeek};
+
+
+/// A temporary directory that is removed when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("fileinput-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Returns the path of `name` within this directory as a string.
+    pub fn join(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A reader that yields its data and then fails with an error of the given kind.
+pub struct FailingIoStream {
+    data: Cursor<Vec<u8>>,
+    kind: ErrorKind,
+}
+
+impl FailingIoStream {
+    pub fn new(data: &[u8], kind: ErrorKind) -> Self {
+        FailingIoStream {
+            data: Cursor::new(data.to_vec()),
+            kind,
+        }
+    }
+}
+
+impl Read for FailingIoStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.data.read(buf)? {
+            0 => Err(io::Error::new(self.kind, "simulated failure")),
+            n => Ok(n),
+        }
+    }
+}
+
+/// A reader that fails its first `read` with `ErrorKind::Interrupted` and then yields its data.
+pub struct InterruptedOnce {
+    data: Cursor<Vec<u8>>,
+    interrupted: bool,
+}
+
+impl Read for InterruptedOnce {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.interrupted {
+            self.interrupted = true;
+            return Err(io::Error::new(ErrorKind::Interrupted, "simulated interruption"));
+        }
+        self.data.read(buf)
+    }
+}
+
+/// A reader that yields at most the given number of bytes per `read`, to exercise data split
+/// across reads.
+pub struct Chunked<R>(pub R, pub usize);
+
+impl<R: Read> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+/// A handle to a mock stdin that, like the real one, is consumed only once.
+#[derive(Clone, Default)]
+struct SharedStdin(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+impl Read for SharedStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+enum MockFile {
+    Data(Vec<u8>),
+    Failing(Vec<u8>, ErrorKind),
+    Interrupted(Vec<u8>),
+}
+
+/// An in-memory `IoStrategy` for tests. Unknown paths are `NotFound`.
+#[derive(Default)]
+pub struct MockIo {
+    files: HashMap<String, MockFile>,
+    stdin: SharedStdin,
+    lens: HashMap<String, u64>,
+    seekable: bool,
+    terminal: bool,
+    trickle: bool,
+}
+
+impl MockIo {
+    pub fn new() -> Self {
+        MockIo::default()
+    }
+
+    pub fn file(mut self, path: &str, data: &[u8]) -> Self {
+        self.files.insert(path.to_string(), MockFile::Data(data.to_vec()));
+        self
+    }
+
+    pub fn failing(mut self, path: &str, data: &[u8], kind: ErrorKind) -> Self {
+        self.files.insert(path.to_string(), MockFile::Failing(data.to_vec(), kind));
+        self
+    }
+
+    /// Adds a file whose first `read` fails with `ErrorKind::Interrupted`.
+    pub fn interrupted(mut self, path: &str, data: &[u8]) -> Self {
+        self.files.insert(path.to_string(), MockFile::Interrupted(data.to_vec()));
+        self
+    }
+
+    /// Makes `metadata` report `len` as the length of the file at `path`, whatever its data.
+    pub fn reported_len(mut self, path: &str, len: u64) -> Self {
+        self.lens.insert(path.to_string(), len);
+        self
+    }
+
+    /// Sets the data read from stdin, which is shared by every reader `IoStrategy::stdin`
+    /// returns.
+    pub fn stdin(mut self, data: &[u8]) -> Self {
+        self.stdin = SharedStdin(Arc::new(Mutex::new(Cursor::new(data.to_vec()))));
+        self
+    }
+
+    /// Makes `open_seekable` open plain data files, which it doesn't by default.
+    pub fn seekable(mut self) -> Self {
+        self.seekable = true;
+        self
+    }
+
+    /// Makes every file yield at most one byte per `read`.
+    pub fn trickle(mut self) -> Self {
+        self.trickle = true;
+        self
+    }
+
+    /// Sets whether stdin claims to be an interactive terminal.
+    pub fn terminal(mut self, terminal: bool) -> Self {
+        self.terminal = terminal;
+        self
+    }
+}
+
+impl IoStrategy for MockIo {
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let reader: Box<dyn Read + Send> = match self.files.get(path) {
+            Some(MockFile::Data(data)) => Box::new(Cursor::new(data.clone())),
+            Some(MockFile::Failing(data, kind)) => Box::new(FailingIoStream::new(data, *kind)),
+            Some(MockFile::Interrupted(data)) => {
+                Box::new(InterruptedOnce { data: Cursor::new(data.clone()), interrupted: false })
+            }
+            None => return Err(io::Error::new(ErrorKind::NotFound, "no such mock file")),
+        };
+        if self.trickle {
+            Ok(Box::new(Chunked(reader, 1)))
+        } else {
+            Ok(reader)
+        }
+    }
+
+    fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(self.stdin.clone()))
+    }
+
+    fn open_seekable(&self, path: &str) -> io::Result<Option<Box<dyn ReadSeek + Send>>> {
+        match self.files.get(path) {
+            Some(MockFile::Data(data)) if self.seekable => {
+                Ok(Some(Box::new(Cursor::new(data.clone()))))
+            }
+            Some(_) => Ok(None),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no such mock file")),
+        }
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        let len = match self.files.get(path) {
+            Some(MockFile::Data(data)) |
+            Some(MockFile::Failing(data, _)) |
+            Some(MockFile::Interrupted(data)) => data.len() as u64,
+            None => return Err(io::Error::new(ErrorKind::NotFound, "no such mock file")),
+        };
+        Ok(Metadata::new(FileKind::File, self.lens.get(path).cloned().unwrap_or(len)))
+    }
+
+    fn stdin_is_terminal(&self) -> bool {
+        self.terminal
+    }
+}
@@ -0,0 +1,329 @@
+//! Concurrent, completion-ordered reading of whole sources.
+use std::io;
+use std::io::Read;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::vec;
+
+use {open_range, source_error, Source, BINARY_PEEK_LEN};
+use strategy::IoStrategy;
+
+
+/// The options of a `FileInput` that `Unordered` applies to each source it reads.
+#[derive(Default)]
+pub(crate) struct Options {
+    pub(crate) budget: Option<usize>,
+    pub(crate) skip_binary: bool,
+    pub(crate) missing_placeholder: Option<Vec<u8>>,
+    pub(crate) buffer_stdin: bool,
+}
+
+/// Tracks how much read-ahead data is waiting to be consumed.
+#[derive(Default)]
+struct Budget {
+    limit: Option<usize>,
+    state: Mutex<BudgetState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct BudgetState {
+    outstanding: usize,
+    closed: bool,
+}
+
+impl Budget {
+    /// Blocks until there's room to read another source, returning false if the consumer has
+    /// gone away.
+    fn wait_for_room(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some(limit) = self.limit {
+            while state.outstanding >= limit && !state.closed {
+                state = self.changed.wait(state).unwrap();
+            }
+        }
+        !state.closed
+    }
+
+    fn add(&self, bytes: usize) {
+        self.state.lock().unwrap().outstanding += bytes;
+    }
+
+    fn release(&self, bytes: usize) {
+        self.state.lock().unwrap().outstanding -= bytes;
+        self.changed.notify_all();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+/// An iterator over the full contents of each source, in the order reads complete.
+///
+/// This struct is created by `FileInput::read_unordered`.
+pub struct Unordered<Io: IoStrategy> {
+    io: Arc<Io>,
+    error: Option<io::Error>,
+    stdin: vec::IntoIter<Source>,
+    stdin_buffer: Option<Vec<u8>>,
+    options: Arc<Options>,
+    results: Receiver<io::Result<(Source, Vec<u8>)>>,
+    budget: Arc<Budget>,
+}
+
+impl<Io> Unordered<Io>
+    where Io: IoStrategy + Send + Sync + 'static
+{
+    pub(crate) fn new(io: Io, sources: Vec<Source>, options: Options) -> Self {
+        let io = Arc::new(io);
+        let options = Arc::new(options);
+        let budget = Arc::new(Budget {
+            limit: options.budget,
+            ..Budget::default()
+        });
+        let (stdin, files): (Vec<Source>, Vec<Source>) =
+            sources.into_iter().partition(Source::is_stdin);
+
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(files.len());
+        let queue = Arc::new(Mutex::new(files));
+        let (tx, rx) = channel();
+
+        for _ in 0..workers {
+            let io = io.clone();
+            let options = options.clone();
+            let queue = queue.clone();
+            let tx = tx.clone();
+            let budget = budget.clone();
+            thread::spawn(move || {
+                while budget.wait_for_room() {
+                    let source = match queue.lock().unwrap().pop() {
+                        Some(source) => source,
+                        None => return,
+                    };
+                    let result = match read_file(&*io, &source, &options) {
+                        Ok(Some(data)) => Ok(data),
+                        Ok(None) => continue,
+                        Err(e) => Err(e),
+                    };
+                    if let Ok(ref data) = result {
+                        budget.add(data.len());
+                    }
+                    if tx.send(result.map(|data| (source, data))).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        Unordered {
+            io,
+            error: None,
+            stdin: stdin.into_iter(),
+            stdin_buffer: None,
+            options,
+            results: rx,
+            budget,
+        }
+    }
+
+    /// Constructs an `Unordered` that yields only an error saying it can't honour `option`.
+    pub(crate) fn unsupported(io: Io, option: &str) -> Self {
+        let message = format!("read_unordered does not support {}", option);
+        let mut unordered = Unordered::new(io, Vec::new(), Options::default());
+        unordered.error = Some(io::Error::new(io::ErrorKind::InvalidInput, message));
+        unordered
+    }
+}
+
+fn read_all(reader: io::Result<Box<dyn Read + Send>>) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Reads the whole of the file `source` like `FileInput` would, returning `None` if it should
+/// be skipped.
+fn read_file<Io: IoStrategy>(io: &Io,
+                             source: &Source,
+                             options: &Options)
+                             -> io::Result<Option<Vec<u8>>> {
+    let reader = match *source {
+        Source::File(ref path) => io.open(path),
+        Source::FileRange { ref path, offset, length } => {
+            open_range(io, path, offset, length).map(|reader| reader as Box<dyn Read + Send>)
+        }
+        _ => {
+            let error = io::Error::new(io::ErrorKind::InvalidInput, "cannot be read concurrently");
+            return Err(source_error(source, error));
+        }
+    };
+    let data = match read_all(reader) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound &&
+                      options.missing_placeholder.is_some() => {
+            options.missing_placeholder.clone().unwrap()
+        }
+        result => result?,
+    };
+
+    if options.skip_binary && is_binary(&data) {
+        return Ok(None);
+    }
+    Ok(Some(data))
+}
+
+/// Returns whether `skip_binary` would skip a source starting with `data`.
+fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_PEEK_LEN as usize)].contains(&0)
+}
+
+impl<Io: IoStrategy> Unordered<Io> {
+    fn read_stdin(&mut self) -> io::Result<Vec<u8>> {
+        if !self.options.buffer_stdin {
+            return read_all(self.io.stdin());
+        }
+        if self.stdin_buffer.is_none() {
+            self.stdin_buffer = Some(read_all(self.io.stdin())?);
+        }
+        Ok(self.stdin_buffer.clone().unwrap())
+    }
+}
+
+impl<Io: IoStrategy> Iterator for Unordered<Io> {
+    type Item = io::Result<(Source, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        while let Some(source) = self.stdin.next() {
+            match self.read_stdin() {
+                Ok(ref data) if self.options.skip_binary && is_binary(data) => {}
+                result => return Some(result.map(|data| (source, data))),
+            }
+        }
+
+        let result = self.results.recv().ok()?;
+        if let Ok((_, ref data)) = result {
+            self.budget.release(data.len());
+        }
+        Some(result)
+    }
+}
+
+impl<Io: IoStrategy> Drop for Unordered<Io> {
+    fn drop(&mut self) {
+        self.budget.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::io;
+    use std::io::{Cursor, ErrorKind, Read};
+    use std::sync::Mutex;
+    use std::sync::mpsc::{channel, Sender};
+    use std::thread;
+
+    use {FileInput, IoStrategy, Source};
+    use test_util::MockIo;
+
+    #[test]
+    fn read_unordered_returns_everything() {
+        let paths = vec!["testdata/1", "testdata/empty", "testdata/2"];
+        let results: HashSet<(Source, Vec<u8>)> = FileInput::new(&paths)
+            .read_unordered()
+            .map(|r| r.unwrap())
+            .collect();
+
+        let expected: HashSet<(Source, Vec<u8>)> =
+            vec![(Source::File("testdata/1".to_string()), b"One.\n".to_vec()),
+                 (Source::File("testdata/empty".to_string()), Vec::new()),
+                 (Source::File("testdata/2".to_string()), b"Two.\nTwo.\n".to_vec())]
+                .into_iter()
+                .collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn read_unordered_applies_per_source_options() {
+        let io = MockIo::new()
+            .file("text", b"Text.\n")
+            .file("binary", b"\0")
+            .stdin(b"Piped.\n");
+        let paths = vec!["text", "binary", "nope", "-", "-"];
+        let mut results: Vec<(Source, Vec<u8>)> = FileInput::with_strategy(&paths, io)
+            .skip_binary()
+            .missing_source_placeholder(b"<missing>".to_vec())
+            .buffer_stdin()
+            .read_unordered()
+            .map(|r| r.unwrap())
+            .collect();
+        results.sort_by(|a, b| a.1.cmp(&b.1));
+
+        assert_eq!(results,
+                   [(Source::File("nope".to_string()), b"<missing>".to_vec()),
+                    (Source::Stdin, b"Piped.\n".to_vec()),
+                    (Source::Stdin, b"Piped.\n".to_vec()),
+                    (Source::File("text".to_string()), b"Text.\n".to_vec())]);
+    }
+
+    #[test]
+    fn read_unordered_rejects_order_dependent_options() {
+        let mut results = FileInput::new(&["testdata/1"]).skip_duplicate_content().read_unordered();
+
+        let error = results.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "read_unordered does not support skip_duplicate_content");
+        assert!(results.next().is_none());
+    }
+
+    /// Reports each file it opens on a channel.
+    struct CountingIo(Mutex<Sender<()>>);
+
+    impl IoStrategy for CountingIo {
+        fn open(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+            self.0.lock().unwrap().send(()).unwrap();
+            Ok(Box::new(Cursor::new(path.as_bytes().to_vec())))
+        }
+
+        fn stdin(&self) -> io::Result<Box<dyn Read + Send>> {
+            Ok(Box::new(io::empty()))
+        }
+    }
+
+    #[test]
+    fn prefetch_memory_budget() {
+        let paths: Vec<String> = (0..32).map(|i| format!("file{:02}", i)).collect();
+        let (tx, opens) = channel();
+        let workers =
+            thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(paths.len());
+        let results = FileInput::with_strategy(&paths, CountingIo(Mutex::new(tx)))
+            .prefetch_memory_budget(1)
+            .read_unordered();
+
+        // Each worker reads one file and then waits for room, which only consuming makes.
+        for _ in 0..workers {
+            opens.recv().unwrap();
+        }
+        assert!(opens.try_recv().is_err());
+
+        let mut opened = workers;
+        let mut contents = Vec::new();
+        for result in results {
+            contents.push(result.unwrap().1);
+            opened += opens.try_iter().count();
+            assert!(opened <= workers + contents.len());
+        }
+        contents.sort();
+        let expected: Vec<Vec<u8>> = paths.iter().map(|p| p.as_bytes().to_vec()).collect();
+        assert_eq!(contents, expected);
+    }
+}